@@ -1,10 +1,10 @@
 use crate::binary::{Operation, RegisterOrNum};
-use crate::typing::Type;
+use crate::error::{CompileError, Span};
+use crate::typing::{usual_arith, Member, Type};
 
 #[derive(Debug, Clone)]
 pub struct ValInfo {
     offset: usize,
-    #[allow(unused)]
     type_name: Type,
 }
 
@@ -12,28 +12,84 @@ impl ValInfo {
     pub fn new(offset: usize, type_name: Type) -> ValInfo {
         ValInfo { offset, type_name }
     }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+#[derive(Debug)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
 }
 
 #[derive(Debug)]
-pub enum Expr {
+pub enum ExprKind {
     Var {
         name: String,
         info: ValInfo,
     },
-    Integer(i32),
+    /// 静的記憶域に置かれる大域変数
+    Global {
+        name: String,
+        type_name: Type,
+    },
+    /// 値と型. 値は型に合わせて 64 ビットに拡張してある
+    Integer(i64, Type),
+    /// 浮動小数点数のリテラル. float なら値を丸めて扱う
+    Float(f64, Type),
     BinOp {
         lhs: Box<Expr>,
         op: Op,
         rhs: Box<Expr>,
     },
+    /// `func` は関数か関数ポインタの式. `ret_buf` は構造体の戻り値を受け取るローカル領域
     FunCall {
-        name: String,
+        func: Box<Expr>,
         args: Vec<Expr>,
+        res_type: Type,
+        ret_buf: Option<usize>,
     },
     Addr(Box<Expr>),
     Dref(Box<Expr>),
+    /// `~`
+    BitNot(Box<Expr>),
+    /// 明示的なキャストと, 代入などでの暗黙の型変換
+    Cast {
+        expr: Box<Expr>,
+        type_name: Type,
+    },
+    /// 条件演算子 `cond ? then : els`
+    Cond {
+        cond: Box<Expr>,
+        then: Box<Expr>,
+        els: Box<Expr>,
+    },
+    /// コンマ演算子. 左辺の値は捨てる
+    Comma {
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    /// 複合代入と `++`, `--`. `post` なら値は変更前のもの
+    OpAssign {
+        lhs: Box<Expr>,
+        op: Op,
+        rhs: Box<Expr>,
+        post: bool,
+    },
+    /// 文字列リテラル. `size` は終端の 0 を含む長さ
+    Str {
+        label: usize,
+        size: usize,
+    },
+    Member {
+        expr: Box<Expr>,
+        member: Member,
+    },
 }
 
+#[allow(clippy::large_enum_variant)]
 pub enum Stmt {
     Calc {
         content: Expr,
@@ -46,36 +102,61 @@ pub enum Stmt {
         t_branch: Box<Stmt>,
         f_branch: Option<Box<Stmt>>,
     },
+    /// `label` は break, continue の飛び先の番号
     While {
         cond: Expr,
         content: Box<Stmt>,
+        label: usize,
     },
     For {
         init: Option<Expr>,
         cond: Option<Expr>,
         tail: Option<Expr>,
         content: Box<Stmt>,
+        label: usize,
+    },
+    DoWhile {
+        content: Box<Stmt>,
+        cond: Expr,
+        label: usize,
     },
+    /// `cases` は case の値とその飛び先の番号
+    Switch {
+        cond: Expr,
+        content: Box<Stmt>,
+        cases: Vec<(i64, usize)>,
+        default: Option<usize>,
+        label: usize,
+    },
+    /// case か default の付いた文
+    Case(usize, Box<Stmt>),
     Block(Vec<Stmt>),
     Declare,
+    Break(usize),
+    Continue(usize),
+    /// ラベルの付いた文. 番号は関数をまたいでも重ならない
+    Labeled(usize, Box<Stmt>),
+    Goto(usize),
 }
 
 pub struct FuncDef {
     name: String,
     #[allow(unused)]
     res_type: Type,
-    args: Vec<(Type, String)>,
+    args: Vec<ValInfo>,
     body: Vec<Stmt>,
     local_area: usize,
+    span: Span,
 }
 
 impl FuncDef {
     pub fn new(
         name: String,
         res_type: Type,
-        args: Vec<(Type, String)>,
+        args: Vec<ValInfo>,
         body: Vec<Stmt>,
         local_area: usize,
+        span: Span,
     ) -> FuncDef {
         FuncDef {
             name,
@@ -83,6 +164,47 @@ impl FuncDef {
             args,
             body,
             local_area,
+            span,
+        }
+    }
+}
+
+/// 大域変数の初期値
+pub enum GlobalInit {
+    Value(i64),
+    /// シンボルのアドレスにバイト数を足したもの. 値はリンク時に決まる
+    Address(String, i64),
+}
+
+pub struct GlobalVar {
+    name: String,
+    type_name: Type,
+    /// 初期値. なければ .bss に置く
+    init: Option<GlobalInit>,
+}
+
+impl GlobalVar {
+    pub fn new(name: String, type_name: Type, init: Option<GlobalInit>) -> GlobalVar {
+        GlobalVar {
+            name,
+            type_name,
+            init,
+        }
+    }
+}
+
+pub struct Program {
+    funcs: Vec<FuncDef>,
+    globals: Vec<GlobalVar>,
+    strings: Vec<Vec<u8>>,
+}
+
+impl Program {
+    pub fn new(funcs: Vec<FuncDef>, globals: Vec<GlobalVar>, strings: Vec<Vec<u8>>) -> Program {
+        Program {
+            funcs,
+            globals,
+            strings,
         }
     }
 }
@@ -93,142 +215,960 @@ pub enum Op {
     Sub,
     Mul,
     Div,
+    Mod,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
     Eq,
     Neq,
     Lt,
     Le,
     Gt,
     Ge,
+    /// `&&`, `||`. 右辺は必要なときだけ評価する
+    LogAnd,
+    LogOr,
     Assign,
 }
 
+/// スタックトップのアドレスから値を読み出してスタックに積む
+///
+/// 配列, 構造体, 関数はアドレスのまま値として扱う.
+fn load(out: &mut Vec<Operation>, ty: &Type) {
+    use Operation::*;
+    use RegisterOrNum::*;
+    match ty {
+        Type::Array(_, _) | Type::Struct(_) | Type::Func(_, _) => return,
+        _ => {}
+    }
+    out.push(Pop(Rax));
+    out.push(Load(rax_of(ty.size()), Rax));
+    extend(out, ty);
+    out.push(Push(Rax));
+}
+
+/// rdi の値を rax の指すアドレスに書き込む. 構造体なら rdi の指す中身を写す
+fn store(out: &mut Vec<Operation>, ty: &Type) {
+    use Operation::*;
+    use RegisterOrNum::*;
+    match ty {
+        Type::Struct(_) => copy(out, ty.size()),
+        _ => out.push(Store(Rax, rdi_of(ty.size()))),
+    }
+}
+
+/// rax のうち下位 `size` バイトを指すレジスタ
+fn rax_of(size: usize) -> RegisterOrNum {
+    match size {
+        1 => RegisterOrNum::Al,
+        2 => RegisterOrNum::Ax,
+        4 => RegisterOrNum::Eax,
+        _ => RegisterOrNum::Rax,
+    }
+}
+
+fn rdi_of(size: usize) -> RegisterOrNum {
+    match size {
+        1 => RegisterOrNum::Dil,
+        2 => RegisterOrNum::Di,
+        4 => RegisterOrNum::Edi,
+        _ => RegisterOrNum::Rdi,
+    }
+}
+
+/// rdi の指す `size` バイトを rax の指す先へ写す. rax と rdi は元の値に戻す
+fn copy(out: &mut Vec<Operation>, size: usize) {
+    use Operation::*;
+    use RegisterOrNum::*;
+    let mut done = 0;
+    while done < size {
+        let step = if size - done >= 8 {
+            out.push(Load(Rdx, Rdi));
+            out.push(Store(Rax, Rdx));
+            8
+        } else {
+            out.push(LoadByte(Rdx, Rdi));
+            out.push(Store(Rax, Dl));
+            1
+        };
+        out.push(Add(Rdi, Num(step)));
+        out.push(Add(Rax, Num(step)));
+        done += step as usize;
+    }
+    if size > 0 {
+        out.push(Sub(Rdi, Num(size as i32)));
+        out.push(Sub(Rax, Num(size as i32)));
+    }
+}
+
+/// 値の受け渡しに使う汎用レジスタの数. 構造体は8バイトごとに1つ使う
+fn eightbytes(ty: &Type) -> usize {
+    match ty {
+        Type::Struct(_) => ty.size().div_ceil(8),
+        _ => 1,
+    }
+}
+
+/// スタックトップのアドレスにある構造体の中身を8バイト単位でスタックに積み直す
+///
+/// 先頭の8バイトがスタックトップに来る. 末尾の余りの中身は不定.
+fn push_struct(out: &mut Vec<Operation>, ty: &Type) {
+    use Operation::*;
+    use RegisterOrNum::*;
+    out.push(Pop(Rdi));
+    out.push(Sub(Rsp, Num(eightbytes(ty) as i32 * 8)));
+    out.push(Mov(Rax, Rsp));
+    copy(out, ty.size());
+}
+
+/// 浮動小数点数のスタック上の表現. float は下位 32 ビットに入れて上位は 0 にする
+fn float_bits(v: f64, ty: &Type) -> i64 {
+    match ty {
+        Type::Float => (v as f32).to_bits() as i64,
+        _ => v.to_bits() as i64,
+    }
+}
+
+fn float_value(bits: i64, ty: &Type) -> f64 {
+    match ty {
+        Type::Float => f32::from_bits(bits as u32) as f64,
+        _ => f64::from_bits(bits as u64),
+    }
+}
+
+/// 64 ビットの即値をスタックに積む
+fn push_imm(out: &mut Vec<Operation>, n: i64) {
+    use Operation::*;
+    use RegisterOrNum::*;
+    match i32::try_from(n) {
+        Ok(n) => out.push(Push(Num(n))),
+        // 32 ビットに収まらない値は即値ごと rax に入れる
+        Err(_) => {
+            out.push(Movabs(Rax, n));
+            out.push(Push(Rax));
+        }
+    }
+}
+
+/// スタックトップの値を取り出して 0 と比べる
+///
+/// 浮動小数点数は符号ビットを追い出してから比べるので, -0.0 も偽になる.
+fn pop_and_test(out: &mut Vec<Operation>, ty: &Type) {
+    use Operation::*;
+    use RegisterOrNum::*;
+    out.push(Pop(Rax));
+    if ty.is_float() {
+        let r = rax_of(ty.size());
+        out.push(Add(r.clone(), r));
+    }
+    out.push(Cmp(Rax, Num(0)));
+}
+
+/// rax の下位にある `ty` の値を 64 ビットに拡張する. 符号なしの型ならゼロ拡張
+fn extend(out: &mut Vec<Operation>, ty: &Type) {
+    use Operation::*;
+    use RegisterOrNum::*;
+    let size = ty.size();
+    if !ty.is_integer() || size == 8 {
+        return;
+    }
+    match (ty.is_unsigned(), size) {
+        (true, 4) => out.push(Mov(Eax, Eax)),
+        (true, _) => out.push(Movzx(Rax, rax_of(size))),
+        (false, _) => out.push(Movsx(Rax, rax_of(size))),
+    }
+}
+
+/// rax の `from` の値を `to` に変換する
+///
+/// スタック上の整数はいつも 64 ビットに拡張しておくので, 整数どうしなら変換元の型によらない.
+/// 浮動小数点数は xmm0 を通して変換する.
+fn convert(out: &mut Vec<Operation>, from: &Type, to: &Type, label_counter: &mut usize) {
+    use Operation::*;
+    use RegisterOrNum::*;
+    match (from.is_float(), to) {
+        // _Bool は 0 か 1 にする
+        (_, Type::Bool) => {
+            if from.is_float() {
+                let r = rax_of(from.size());
+                out.push(Add(r.clone(), r));
+            }
+            out.push(Cmp(Rax, Num(0)));
+            out.push(Setne(Al));
+            out.push(Movzb(Rax, Al));
+        }
+        (false, Type::Float | Type::Double) => {
+            let cvt = |r| match to {
+                Type::Float => Cvtsi2ss(Xmm(0), r),
+                _ => Cvtsi2sd(Xmm(0), r),
+            };
+            if *from != Type::ULong {
+                out.push(cvt(Rax));
+                from_xmm0(out, to);
+                return;
+            }
+            // 2^63 以上の unsigned long は半分にしてから変換して2倍する.
+            // 捨てる最下位ビットを残しておくと丸めが正しくなる
+            *label_counter += 1;
+            let label = *label_counter;
+            out.push(Cmp(Rax, Num(0)));
+            out.push(Js("ubig", label));
+            out.push(cvt(Rax));
+            out.push(Jmp("uend", label));
+            out.push(Label("ubig", label));
+            out.push(Mov(Rdx, Rax));
+            out.push(Mov(Rcx, Num(1)));
+            out.push(Shr(Rdx, Cl));
+            out.push(And(Rax, Num(1)));
+            out.push(Or(Rdx, Rax));
+            out.push(cvt(Rdx));
+            match to {
+                Type::Float => out.push(Addss(Xmm(0), Xmm(0))),
+                _ => out.push(Addsd(Xmm(0), Xmm(0))),
+            }
+            out.push(Label("uend", label));
+            from_xmm0(out, to);
+        }
+        (true, Type::Float | Type::Double) => {
+            if from == to {
+                return;
+            }
+            out.push(Movq(Xmm(0), Rax));
+            match to {
+                Type::Float => out.push(Cvtsd2ss(Xmm(0), Xmm(0))),
+                _ => out.push(Cvtss2sd(Xmm(0), Xmm(0))),
+            }
+            from_xmm0(out, to);
+        }
+        (true, _) => {
+            out.push(Movq(Xmm(0), Rax));
+            let cvt = || match from {
+                Type::Float => Cvttss2si(Rax, Xmm(0)),
+                _ => Cvttsd2si(Rax, Xmm(0)),
+            };
+            out.push(cvt());
+            if *to == Type::ULong {
+                // 2^63 以上は変換に失敗して負になるので, 2^63 を引いてから変換して最上位ビットを立てる
+                *label_counter += 1;
+                let label = *label_counter;
+                out.push(Cmp(Rax, Num(0)));
+                out.push(Js("ubig", label));
+                out.push(Jmp("uend", label));
+                out.push(Label("ubig", label));
+                out.push(Movabs(Rax, float_bits(9223372036854775808.0, from)));
+                out.push(Movq(Xmm(1), Rax));
+                match from {
+                    Type::Float => out.push(Subss(Xmm(0), Xmm(1))),
+                    _ => out.push(Subsd(Xmm(0), Xmm(1))),
+                }
+                out.push(cvt());
+                out.push(Movabs(Rdx, i64::MIN));
+                out.push(Xor(Rax, Rdx));
+                out.push(Label("uend", label));
+            }
+            extend(out, to);
+        }
+        (false, _) => extend(out, to),
+    }
+}
+
+/// xmm0 にある `ty` の浮動小数点数を rax に移す. float なら上位 32 ビットは 0 にする
+fn from_xmm0(out: &mut Vec<Operation>, ty: &Type) {
+    use Operation::*;
+    use RegisterOrNum::*;
+    out.push(Movq(Rax, Xmm(0)));
+    if *ty == Type::Float {
+        out.push(Mov(Eax, Eax));
+    }
+}
+
+/// 二項演算をする型. シフトは左辺の型, それ以外は通常の算術型変換の結果
+fn operation_type(lhs: &Type, op: &Op, rhs: &Type) -> Type {
+    match op {
+        Op::Shl | Op::Shr => lhs.promote(),
+        _ => usual_arith(lhs, rhs),
+    }
+}
+
+/// rax と rdi の演算結果を rax に入れる. 除算, 比較, 右シフトは `ty` の符号の有無で命令を変える
+fn arith(out: &mut Vec<Operation>, op: &Op, ty: &Type) {
+    use Operation::*;
+    use RegisterOrNum::*;
+    if ty.is_float() {
+        return arith_float(out, op, ty);
+    }
+    let unsigned = ty.is_unsigned();
+    match op {
+        Op::Add => out.push(Add(Rax, Rdi)),
+        Op::Sub => out.push(Sub(Rax, Rdi)),
+        Op::Mul => out.push(Imul(Rax, Rdi)),
+        Op::Div | Op::Mod => {
+            if unsigned {
+                out.push(Mov(Rdx, Num(0)));
+                out.push(Div(Rdi));
+            } else {
+                out.push(Cqo);
+                out.push(Idiv(Rdi));
+            }
+            // 余りは rdx に入る
+            if *op == Op::Mod {
+                out.push(Mov(Rax, Rdx));
+            }
+        }
+        Op::BitAnd => out.push(And(Rax, Rdi)),
+        Op::BitOr => out.push(Or(Rax, Rdi)),
+        Op::BitXor => out.push(Xor(Rax, Rdi)),
+        Op::Shl => {
+            out.push(Mov(Rcx, Rdi));
+            out.push(Sal(Rax, Cl))
+        }
+        Op::Shr => {
+            out.push(Mov(Rcx, Rdi));
+            if unsigned {
+                out.push(Shr(Rax, Cl))
+            } else {
+                out.push(Sar(Rax, Cl))
+            }
+        }
+        Op::Eq => {
+            out.push(Cmp(Rax, Rdi));
+            out.push(Sete(Al));
+            out.push(Movzb(Rax, Al))
+        }
+        Op::Neq => {
+            out.push(Cmp(Rax, Rdi));
+            out.push(Setne(Al));
+            out.push(Movzb(Rax, Al))
+        }
+        // > と >= は左右を入れ替えて < と <= にする
+        Op::Lt | Op::Le | Op::Gt | Op::Ge => {
+            match op {
+                Op::Lt | Op::Le => out.push(Cmp(Rax, Rdi)),
+                _ => out.push(Cmp(Rdi, Rax)),
+            }
+            match (op, unsigned) {
+                (Op::Lt | Op::Gt, false) => out.push(Setl(Al)),
+                (Op::Le | Op::Ge, false) => out.push(Setle(Al)),
+                (Op::Lt | Op::Gt, true) => out.push(Setb(Al)),
+                _ => out.push(Setbe(Al)),
+            }
+            out.push(Movzb(Rax, Al))
+        }
+        Op::Assign | Op::LogAnd | Op::LogOr => unreachable!(),
+    }
+}
+
+/// 浮動小数点数の演算. xmm0 と xmm1 に移して計算する
+fn arith_float(out: &mut Vec<Operation>, op: &Op, ty: &Type) {
+    use Operation::*;
+    use RegisterOrNum::*;
+    let (x, y) = (Xmm(0), Xmm(1));
+    out.push(Movq(x.clone(), Rax));
+    out.push(Movq(y.clone(), Rdi));
+    let double = *ty == Type::Double;
+    let compare = |out: &mut Vec<Operation>, a: RegisterOrNum, b: RegisterOrNum| {
+        if double {
+            out.push(Ucomisd(a, b));
+        } else {
+            out.push(Ucomiss(a, b));
+        }
+    };
+    match op {
+        Op::Add | Op::Sub | Op::Mul | Op::Div => {
+            out.push(match (op, double) {
+                (Op::Add, true) => Addsd(x, y),
+                (Op::Sub, true) => Subsd(x, y),
+                (Op::Mul, true) => Mulsd(x, y),
+                (Op::Div, true) => Divsd(x, y),
+                (Op::Add, false) => Addss(x, y),
+                (Op::Sub, false) => Subss(x, y),
+                (Op::Mul, false) => Mulss(x, y),
+                _ => Divss(x, y),
+            });
+            return from_xmm0(out, ty);
+        }
+        // NaN と比べると PF が立つ. NaN はどれとも等しくない
+        Op::Eq => {
+            compare(out, x, y);
+            out.push(Sete(Al));
+            out.push(Setnp(Dl));
+            out.push(And(Al, Dl));
+        }
+        Op::Neq => {
+            compare(out, x, y);
+            out.push(Setne(Al));
+            out.push(Setp(Dl));
+            out.push(Or(Al, Dl));
+        }
+        // < と <= は左右を入れ替えて > と >= にする. NaN との比較は偽になる
+        Op::Lt | Op::Le | Op::Gt | Op::Ge => {
+            match op {
+                Op::Lt | Op::Le => compare(out, y, x),
+                _ => compare(out, x, y),
+            }
+            match op {
+                Op::Lt | Op::Gt => out.push(Seta(Al)),
+                _ => out.push(Setae(Al)),
+            }
+        }
+        _ => unreachable!(),
+    }
+    out.push(Movzb(Rax, Al));
+}
+
+const ARG_REGS: [RegisterOrNum; 6] = [
+    RegisterOrNum::Rdi,
+    RegisterOrNum::Rsi,
+    RegisterOrNum::Rdx,
+    RegisterOrNum::Rcx,
+    RegisterOrNum::R8,
+    RegisterOrNum::R9,
+];
+
 pub trait GenAssembly {
-    fn to_assembly(&self, out: &mut Vec<Operation>, label_counter: &mut usize) -> ();
+    fn to_assembly(
+        &self,
+        out: &mut Vec<Operation>,
+        label_counter: &mut usize,
+    ) -> Result<(), CompileError>;
 }
 
 impl Expr {
-    fn gen_lval(&self, out: &mut Vec<Operation>, label_counter: &mut usize) -> () {
+    pub fn new(kind: ExprKind, span: impl Into<Span>) -> Expr {
+        Expr {
+            kind,
+            span: span.into(),
+        }
+    }
+
+    pub fn get_type(&self) -> Type {
+        match &self.kind {
+            ExprKind::Var { name: _, info } => info.type_name.clone(),
+            ExprKind::Global { name: _, type_name } => type_name.clone(),
+            ExprKind::Integer(_, type_name) | ExprKind::Float(_, type_name) => type_name.clone(),
+            ExprKind::BinOp { lhs, op, rhs } => match op {
+                Op::Assign => lhs.get_type(),
+                Op::Add | Op::Sub => {
+                    let (l, r) = (lhs.get_type(), rhs.get_type());
+                    match (l.base(), r.base()) {
+                        // ポインタどうしの差
+                        (Some(_), Some(_)) => Type::Long,
+                        (Some(t), None) | (None, Some(t)) => Type::Ptr(Box::new(t.clone())),
+                        _ => usual_arith(&l, &r),
+                    }
+                }
+                Op::Mul
+                | Op::Div
+                | Op::Mod
+                | Op::BitAnd
+                | Op::BitOr
+                | Op::BitXor
+                | Op::Shl
+                | Op::Shr => operation_type(&lhs.get_type(), op, &rhs.get_type()),
+                _ => Type::Int,
+            },
+            ExprKind::FunCall { res_type, .. } => res_type.clone(),
+            ExprKind::Addr(e) => Type::Ptr(Box::new(e.get_type())),
+            ExprKind::BitNot(e) => e.get_type().promote(),
+            ExprKind::Cast { expr: _, type_name } => type_name.clone(),
+            // 配列はポインタになる. 片方だけポインタならそちらの型
+            ExprKind::Cond { cond: _, then, els } => {
+                let (t, e) = (then.get_type(), els.get_type());
+                match (t.base(), e.base()) {
+                    (Some(b), _) | (None, Some(b)) => Type::Ptr(Box::new(b.clone())),
+                    _ => t,
+                }
+            }
+            ExprKind::Comma { lhs: _, rhs } => rhs.get_type(),
+            ExprKind::OpAssign { lhs, .. } => lhs.get_type(),
+            ExprKind::Dref(e) => e.get_type().base().unwrap().clone(),
+            ExprKind::Str { label: _, size } => Type::Array(Box::new(Type::Char), *size),
+            ExprKind::Member { expr: _, member } => member.type_name.clone(),
+        }
+    }
+
+    /// 定数式ならその値を計算する. 浮動小数点数はスタック上と同じビット列になる
+    pub fn eval(&self) -> Option<i64> {
+        match &self.kind {
+            ExprKind::Integer(n, _) => Some(*n),
+            ExprKind::Float(v, ty) => Some(float_bits(*v, ty)),
+            ExprKind::BinOp {
+                lhs,
+                op: op @ (Op::LogAnd | Op::LogOr),
+                rhs,
+            } => {
+                let l = lhs.eval_truth()?;
+                // 左辺で結果が決まれば右辺は定数でなくてもよい
+                if l == (*op == Op::LogOr) {
+                    return Some(l as i64);
+                }
+                Some(rhs.eval_truth()? as i64)
+            }
+            ExprKind::BitNot(e) => Some(self.get_type().wrap(!e.eval()?)),
+            ExprKind::Cast { expr, type_name } => {
+                let from = expr.get_type();
+                let n = expr.eval()?;
+                match (from.is_float(), type_name) {
+                    (true, Type::Bool) => Some((float_value(n, &from) != 0.0) as i64),
+                    (true, Type::Float | Type::Double) => {
+                        Some(float_bits(float_value(n, &from), type_name))
+                    }
+                    (true, _) => {
+                        let v = float_value(n, &from);
+                        let n = if type_name.is_unsigned() {
+                            v as u64 as i64
+                        } else {
+                            v as i64
+                        };
+                        Some(type_name.wrap(n))
+                    }
+                    (false, Type::Float | Type::Double) => {
+                        let v = if from.is_unsigned() {
+                            n as u64 as f64
+                        } else {
+                            n as f64
+                        };
+                        Some(float_bits(v, type_name))
+                    }
+                    (false, _) => Some(type_name.wrap(n)),
+                }
+            }
+            ExprKind::Cond { cond, then, els } => {
+                if cond.eval_truth()? {
+                    then.eval()
+                } else {
+                    els.eval()
+                }
+            }
+            ExprKind::BinOp { op: Op::Assign, .. } => None,
+            ExprKind::BinOp { lhs, op, rhs } => {
+                // 符号なしの 64 ビットの値も比較や除算ができるように広げて計算する
+                let ty = operation_type(&lhs.get_type(), op, &rhs.get_type());
+                // 両辺とも演算する型に変換してある
+                if ty.is_float() {
+                    let (l, r) = (float_value(lhs.eval()?, &ty), float_value(rhs.eval()?, &ty));
+                    let v = match op {
+                        Op::Add => l + r,
+                        Op::Sub => l - r,
+                        Op::Mul => l * r,
+                        Op::Div => l / r,
+                        Op::Eq => return Some((l == r) as i64),
+                        Op::Neq => return Some((l != r) as i64),
+                        Op::Lt => return Some((l < r) as i64),
+                        Op::Le => return Some((l <= r) as i64),
+                        Op::Gt => return Some((l > r) as i64),
+                        Op::Ge => return Some((l >= r) as i64),
+                        _ => unreachable!(),
+                    };
+                    return Some(float_bits(v, &ty));
+                }
+                let widen = |n: i64| {
+                    if ty.is_unsigned() {
+                        n as u64 as i128
+                    } else {
+                        n as i128
+                    }
+                };
+                let (l, r) = (widen(lhs.eval()?), widen(rhs.eval()?));
+                let n = match op {
+                    Op::Add => l.wrapping_add(r),
+                    Op::Sub => l.wrapping_sub(r),
+                    Op::Mul => l.wrapping_mul(r),
+                    Op::Div => l.checked_div(r)?,
+                    Op::Mod => l.checked_rem(r)?,
+                    Op::BitAnd => l & r,
+                    Op::BitOr => l | r,
+                    Op::BitXor => l ^ r,
+                    Op::Shl => l.wrapping_shl(r as u32),
+                    Op::Shr => l.wrapping_shr(r as u32),
+                    Op::Eq => (l == r) as i128,
+                    Op::Neq => (l != r) as i128,
+                    Op::Lt => (l < r) as i128,
+                    Op::Le => (l <= r) as i128,
+                    Op::Gt => (l > r) as i128,
+                    Op::Ge => (l >= r) as i128,
+                    Op::Assign | Op::LogAnd | Op::LogOr => unreachable!(),
+                };
+                Some(self.get_type().wrap(n as i64))
+            }
+            _ => None,
+        }
+    }
+
+    /// 定数式の真偽
+    /// アドレス定数を, シンボルとそこからのバイト数として求める
+    pub fn eval_address(&self) -> Option<(String, i64)> {
+        // 配列と関数はその番地を指すポインタになる
+        if let Type::Array(_, _) | Type::Func(_, _) = self.get_type() {
+            return self.eval_lval();
+        }
+        match &self.kind {
+            ExprKind::Addr(e) => e.eval_lval(),
+            ExprKind::Cast {
+                expr,
+                type_name: Type::Ptr(_),
+            } => expr.eval_address(),
+            // 整数の側は要素の大きさを掛けてある
+            ExprKind::BinOp {
+                lhs,
+                op: op @ (Op::Add | Op::Sub),
+                rhs,
+            } => {
+                let (name, offset) = lhs.eval_address()?;
+                let n = rhs.eval()?;
+                match op {
+                    Op::Add => Some((name, offset.wrapping_add(n))),
+                    _ => Some((name, offset.wrapping_sub(n))),
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// 静的記憶域にある左辺値の番地
+    fn eval_lval(&self) -> Option<(String, i64)> {
+        match &self.kind {
+            ExprKind::Global { name, type_name: _ } => Some((name.clone(), 0)),
+            ExprKind::Str { label, size: _ } => Some((format!(".Lstr{}", label), 0)),
+            ExprKind::Member { expr, member } => {
+                let (name, offset) = expr.eval_lval()?;
+                Some((name, offset + member.offset as i64))
+            }
+            ExprKind::Dref(e) => e.eval_address(),
+            _ => None,
+        }
+    }
+
+    fn eval_truth(&self) -> Option<bool> {
+        let n = self.eval()?;
+        let ty = self.get_type();
+        if ty.is_float() {
+            Some(float_value(n, &ty) != 0.0)
+        } else {
+            Some(n != 0)
+        }
+    }
+
+    fn gen_lval(
+        &self,
+        out: &mut Vec<Operation>,
+        label_counter: &mut usize,
+    ) -> Result<(), CompileError> {
         use Operation::*;
         use RegisterOrNum::*;
-        match self {
-            Expr::Var { name: _, info } => {
+        match &self.kind {
+            ExprKind::Var { name: _, info } => {
                 out.push(Mov(Rax, Rbp));
-                out.push(Sub(Rax, Num(info.offset.clone() as i32)));
+                out.push(Sub(Rax, Num(info.offset as i32)));
                 out.push(Push(Rax));
             }
-            Expr::Dref(v) => {
-                v.to_assembly(out, label_counter);
+            // 関数は共有ライブラリにあるかもしれないので GOT を通してアドレスを得る
+            ExprKind::Global {
+                name,
+                type_name: Type::Func(_, _),
+            } => {
+                out.push(LoadGot(Rax, name.clone()));
+                out.push(Push(Rax));
+            }
+            ExprKind::Global { name, type_name: _ } => {
+                out.push(Lea(Rax, name.clone()));
+                out.push(Push(Rax));
+            }
+            ExprKind::Dref(v) => {
+                v.to_assembly(out, label_counter)?;
             }
-            _ => panic!("代入の左辺値が変数ではありません"),
+            ExprKind::Member { expr, member } => {
+                // 構造体の値はそのアドレスになっている
+                expr.to_assembly(out, label_counter)?;
+                out.push(Pop(Rax));
+                out.push(Add(Rax, Num(member.offset as i32)));
+                out.push(Push(Rax));
+            }
+            _ => return Err(CompileError::new(self.span, "expression is not assignable")),
         }
+        Ok(())
     }
 }
 
 impl GenAssembly for Expr {
-    fn to_assembly(&self, out: &mut Vec<Operation>, label_counter: &mut usize) -> () {
+    fn to_assembly(
+        &self,
+        out: &mut Vec<Operation>,
+        label_counter: &mut usize,
+    ) -> Result<(), CompileError> {
         use Operation::*;
         use RegisterOrNum::*;
-        match self {
-            Expr::Var { name: _, info: _ } => {
-                self.gen_lval(out, label_counter);
-                out.push(Pop(Rax));
-                out.push(Load(Rax, Rax));
-                out.push(Push(Rax));
+        match &self.kind {
+            ExprKind::Var { name: _, info: _ }
+            | ExprKind::Global {
+                name: _,
+                type_name: _,
             }
-            Expr::Integer(n) => {
-                out.push(Push(Num(n.clone())));
+            | ExprKind::Member { .. } => {
+                self.gen_lval(out, label_counter)?;
+                load(out, &self.get_type());
             }
-            Expr::BinOp { lhs, op, rhs } => {
+            ExprKind::Integer(n, _) => push_imm(out, *n),
+            ExprKind::Float(v, ty) => push_imm(out, float_bits(*v, ty)),
+            ExprKind::BinOp { lhs, op, rhs } => {
                 if *op == Op::Assign {
-                    lhs.gen_lval(out, label_counter);
-                    rhs.to_assembly(out, label_counter);
+                    lhs.gen_lval(out, label_counter)?;
+                    rhs.to_assembly(out, label_counter)?;
                     out.push(Pop(Rdi));
                     out.push(Pop(Rax));
-                    out.push(Store(Rax, Rdi));
-                    out.push(Push(Rdi));
-                    return;
+                    let ty = lhs.get_type();
+                    store(out, &ty);
+                    match ty {
+                        Type::Struct(_) => out.push(Push(Rax)),
+                        _ => out.push(Push(Rdi)),
+                    }
+                    return Ok(());
+                }
+                if let Op::LogAnd | Op::LogOr = op {
+                    // 左辺で決まるときは else に飛んで結果を積む. 決まらなければ右辺の真偽が結果
+                    lhs.to_assembly(out, label_counter)?;
+                    pop_and_test(out, &lhs.get_type());
+                    *label_counter += 1;
+                    let crr_label = *label_counter;
+                    let decided = (*op == Op::LogOr) as i32;
+                    if *op == Op::LogAnd {
+                        out.push(Je("else", crr_label));
+                    } else {
+                        out.push(Je("begin", crr_label));
+                        out.push(Jmp("else", crr_label));
+                        out.push(Label("begin", crr_label));
+                    }
+                    rhs.to_assembly(out, label_counter)?;
+                    pop_and_test(out, &rhs.get_type());
+                    out.push(Setne(Al));
+                    out.push(Movzb(Rax, Al));
+                    out.push(Push(Rax));
+                    out.push(Jmp("end", crr_label));
+                    out.push(Label("else", crr_label));
+                    out.push(Push(Num(decided)));
+                    out.push(Label("end", crr_label));
+                    return Ok(());
                 }
-                lhs.to_assembly(out, label_counter);
-                rhs.to_assembly(out, label_counter);
+                lhs.to_assembly(out, label_counter)?;
+                rhs.to_assembly(out, label_counter)?;
                 out.push(Pop(Rdi));
                 out.push(Pop(Rax));
-                match op {
-                    Op::Add => out.push(Add(Rax, Rdi)),
-                    Op::Sub => out.push(Sub(Rax, Rdi)),
-                    Op::Mul => out.push(Imul(Rax, Rdi)),
-                    Op::Div => {
-                        out.push(Cqo);
-                        out.push(Idiv(Rdi))
-                    }
-                    Op::Eq => {
-                        out.push(Cmp(Rax, Rdi));
-                        out.push(Sete(Al));
-                        out.push(Movzb(Rax, Al))
+                arith(
+                    out,
+                    op,
+                    &operation_type(&lhs.get_type(), op, &rhs.get_type()),
+                );
+                // 幅の狭い型の演算結果は切り詰める
+                extend(out, &self.get_type());
+                out.push(Push(Rax));
+            }
+            ExprKind::FunCall {
+                func,
+                args,
+                res_type,
+                ret_buf,
+            } => {
+                // 浮動小数点数は xmm レジスタ, それ以外は汎用レジスタで渡す
+                let (fargs, iargs): (Vec<&Expr>, Vec<&Expr>) =
+                    args.iter().partition(|x| x.get_type().is_float());
+                let regs: usize = iargs.iter().map(|x| eightbytes(&x.get_type())).sum();
+                if regs > ARG_REGS.len() {
+                    return Err(CompileError::new(
+                        self.span,
+                        format!(
+                            "too many arguments: at most 6 registers are supported, {} needed",
+                            regs
+                        ),
+                    ));
+                }
+                if fargs.len() > 8 {
+                    return Err(CompileError::new(
+                        self.span,
+                        format!(
+                            "too many floating-point arguments: at most 8 registers are supported, {} needed",
+                            fargs.len()
+                        ),
+                    ));
+                }
+                // 名前で呼べない関数はアドレスを引数より先に積んでおく
+                let direct = match &func.kind {
+                    ExprKind::Global {
+                        name,
+                        type_name: Type::Func(_, _),
+                    } => Some(name),
+                    _ => None,
+                };
+                if direct.is_none() {
+                    func.to_assembly(out, label_counter)?;
+                }
+                for i in args {
+                    i.to_assembly(out, label_counter)?;
+                    let ty = i.get_type();
+                    if let Type::Struct(_) = ty {
+                        push_struct(out, &ty);
                     }
-                    Op::Neq => {
-                        out.push(Cmp(Rax, Rdi));
-                        out.push(Setne(Al));
-                        out.push(Movzb(Rax, Al))
+                }
+                // 後ろの引数から取り出す. 構造体は先頭の8バイトが上に積まれている
+                let (mut next, mut fnext) = (regs, fargs.len());
+                for i in args.iter().rev() {
+                    if i.get_type().is_float() {
+                        fnext -= 1;
+                        out.push(Pop(Rax));
+                        out.push(Movq(Xmm(fnext as u8), Rax));
+                        continue;
                     }
-                    Op::Lt => {
-                        out.push(Cmp(Rax, Rdi));
-                        out.push(Setl(Al));
-                        out.push(Movzb(Rax, Al))
+                    let n = eightbytes(&i.get_type());
+                    next -= n;
+                    for r in &ARG_REGS[next..next + n] {
+                        out.push(Pop(r.clone()));
                     }
-                    Op::Le => {
-                        out.push(Cmp(Rax, Rdi));
-                        out.push(Setle(Al));
-                        out.push(Movzb(Rax, Al))
+                }
+                if direct.is_none() {
+                    out.push(Pop(R10));
+                }
+                // rsp を16バイト境界に揃えてから呼ぶ. 元の rsp は2回積んでおく
+                out.push(Mov(Rax, Rsp));
+                out.push(And(Rsp, Num(-16)));
+                out.push(Push(Rax));
+                out.push(Push(Rax));
+                // 可変長引数の関数のために al にベクタレジスタの数を入れる
+                out.push(Mov(Rax, Num(fargs.len() as i32)));
+                match direct {
+                    Some(name) => out.push(Call(name.clone())),
+                    None => out.push(CallReg(R10)),
+                }
+                out.push(Pop(Rdi));
+                out.push(Pop(Rsp));
+                match ret_buf {
+                    // 構造体は rax, rdx で返ってくるので領域に書き出してそのアドレスを値にする
+                    Some(offset) => {
+                        out.push(Mov(Rdi, Rbp));
+                        out.push(Sub(Rdi, Num(*offset as i32)));
+                        out.push(Store(Rdi, Rax));
+                        if eightbytes(res_type) == 2 {
+                            out.push(Add(Rdi, Num(8)));
+                            out.push(Store(Rdi, Rdx));
+                            out.push(Sub(Rdi, Num(8)));
+                        }
+                        out.push(Push(Rdi));
                     }
-                    Op::Gt => {
-                        out.push(Cmp(Rdi, Rax));
-                        out.push(Setl(Al));
-                        out.push(Movzb(Rax, Al))
+                    None if res_type.is_float() => {
+                        from_xmm0(out, res_type);
+                        out.push(Push(Rax));
                     }
-                    Op::Ge => {
-                        out.push(Cmp(Rdi, Rax));
-                        out.push(Setle(Al));
-                        out.push(Movzb(Rax, Al))
+                    // 戻り値は型の幅の分しか決まっていない
+                    None => {
+                        extend(out, res_type);
+                        out.push(Push(Rax));
                     }
-                    Op::Assign => panic!(),
                 }
+            }
+            ExprKind::Addr(e) => {
+                e.gen_lval(out, label_counter)?;
+            }
+            ExprKind::Dref(e) => {
+                e.to_assembly(out, label_counter)?;
+                load(out, &self.get_type());
+            }
+            ExprKind::BitNot(e) => {
+                e.to_assembly(out, label_counter)?;
+                out.push(Pop(Rax));
+                out.push(Not(Rax));
+                extend(out, &self.get_type());
                 out.push(Push(Rax));
             }
-            Expr::FunCall { name, args } => {
-                for i in args {
-                    i.to_assembly(out, label_counter);
-                }
-                let args_num = args.len();
-                assert!(args_num <= 6, "関数の引数は6つまででです。");
-                out.push(Mov(Rax, Num(args_num as i32)));
-                let arg_regi = vec![Rdi, Rsi, Rdx, Rcx, R8, R9];
-                for i in (0..args_num).rev() {
-                    out.push(Pop(arg_regi[i].clone()));
-                }
-                out.push(Call(name.clone()));
+            ExprKind::Cast { expr, type_name } => {
+                expr.to_assembly(out, label_counter)?;
+                out.push(Pop(Rax));
+                convert(out, &expr.get_type(), type_name, label_counter);
                 out.push(Push(Rax));
             }
-            Expr::Addr(e) => {
-                e.gen_lval(out, label_counter);
+            ExprKind::Cond { cond, then, els } => {
+                cond.to_assembly(out, label_counter)?;
+                pop_and_test(out, &cond.get_type());
+                *label_counter += 1;
+                let crr_label = *label_counter;
+                out.push(Je("else", crr_label));
+                then.to_assembly(out, label_counter)?;
+                out.push(Jmp("end", crr_label));
+                out.push(Label("else", crr_label));
+                els.to_assembly(out, label_counter)?;
+                out.push(Label("end", crr_label));
+            }
+            ExprKind::Comma { lhs, rhs } => {
+                lhs.to_assembly(out, label_counter)?;
+                out.push(Pop(Rax));
+                rhs.to_assembly(out, label_counter)?;
             }
-            Expr::Dref(e) => {
-                e.to_assembly(out, label_counter);
+            ExprKind::OpAssign { lhs, op, rhs, post } => {
+                // アドレスを複製して片方で今の値を読む
+                let ty = lhs.get_type();
+                lhs.gen_lval(out, label_counter)?;
                 out.push(Pop(Rax));
-                out.push(Load(Rax, Rax));
+                out.push(Push(Rax));
+                out.push(Push(Rax));
+                load(out, &ty);
+                if *post {
+                    // 変更前の値をアドレスの下に置いておく
+                    out.push(Pop(Rdi));
+                    out.push(Pop(Rax));
+                    out.push(Push(Rdi));
+                    out.push(Push(Rax));
+                    out.push(Push(Rdi));
+                }
+                rhs.to_assembly(out, label_counter)?;
+                out.push(Pop(Rdi));
+                out.push(Pop(Rax));
+                // 左辺の値を演算する型に変換してから計算し, 左辺の型に戻す
+                let op_type = operation_type(&ty, op, &rhs.get_type());
+                convert(out, &ty, &op_type, label_counter);
+                arith(out, op, &op_type);
+                convert(out, &op_type, &ty, label_counter);
+                out.push(Mov(Rdi, Rax));
+                out.push(Pop(Rax));
+                store(out, &ty);
+                if !*post {
+                    out.push(Push(Rdi));
+                }
+            }
+
+            ExprKind::Str { label, size: _ } => {
+                out.push(Lea(Rax, format!(".Lstr{}", label)));
                 out.push(Push(Rax));
             }
         }
+        Ok(())
     }
 }
 
 impl GenAssembly for Stmt {
-    fn to_assembly(&self, out: &mut Vec<Operation>, label_counter: &mut usize) {
+    fn to_assembly(
+        &self,
+        out: &mut Vec<Operation>,
+        label_counter: &mut usize,
+    ) -> Result<(), CompileError> {
         use Operation::*;
         use RegisterOrNum::*;
         match self {
             Stmt::Return { expr } => {
-                expr.to_assembly(out, label_counter);
-                out.push(Pop(Rax));
+                expr.to_assembly(out, label_counter)?;
+                let ty = expr.get_type();
+                if let Type::Struct(_) = ty {
+                    // 16バイト以下の構造体は rax, rdx に詰めて返す
+                    push_struct(out, &ty);
+                    out.push(Pop(Rax));
+                    if eightbytes(&ty) == 2 {
+                        out.push(Pop(Rdx));
+                    }
+                } else {
+                    out.push(Pop(Rax));
+                    // 浮動小数点数は xmm0 で返す
+                    if ty.is_float() {
+                        out.push(Movq(Xmm(0), Rax));
+                    }
+                }
                 out.push(Mov(Rsp, Rbp));
                 out.push(Pop(Rbp));
                 out.push(Ret);
@@ -238,97 +1178,258 @@ impl GenAssembly for Stmt {
                 t_branch,
                 f_branch,
             } => {
-                cond.to_assembly(out, label_counter);
-                out.push(Pop(Rax));
-                out.push(Cmp(Rax, Num(0)));
+                cond.to_assembly(out, label_counter)?;
+                pop_and_test(out, &cond.get_type());
                 let crr_label = *label_counter + 1;
-                *label_counter = *label_counter + 1;
-                if let Some(f_branch) = &*f_branch {
+                *label_counter += 1;
+                if let Some(f_branch) = f_branch {
                     out.push(Je("else", crr_label));
-                    t_branch.to_assembly(out, label_counter);
+                    t_branch.to_assembly(out, label_counter)?;
                     out.push(Jmp("end", crr_label));
                     out.push(Label("else", crr_label));
-                    f_branch.to_assembly(out, label_counter);
+                    f_branch.to_assembly(out, label_counter)?;
                     out.push(Label("end", crr_label));
                 } else {
                     out.push(Je("end", crr_label));
-                    t_branch.to_assembly(out, label_counter);
+                    t_branch.to_assembly(out, label_counter)?;
                     out.push(Label("end", crr_label));
                 }
             }
             Stmt::Block(v) => {
                 for i in v {
-                    i.to_assembly(out, label_counter);
+                    i.to_assembly(out, label_counter)?;
                 }
             }
-            Stmt::While { cond, content } => {
+            Stmt::While {
+                cond,
+                content,
+                label,
+            } => {
                 let crr_label = *label_counter + 1;
-                *label_counter = *label_counter + 1;
+                *label_counter += 1;
                 out.push(Label("begin", crr_label));
-                cond.to_assembly(out, label_counter);
-                out.push(Pop(Rax));
-                out.push(Cmp(Rax, Num(0)));
+                out.push(Label("continue", *label));
+                cond.to_assembly(out, label_counter)?;
+                pop_and_test(out, &cond.get_type());
                 out.push(Je("end", crr_label));
-                content.to_assembly(out, label_counter);
+                content.to_assembly(out, label_counter)?;
                 out.push(Jmp("begin", crr_label));
                 out.push(Label("end", crr_label));
+                out.push(Label("break", *label));
             }
             Stmt::For {
                 init,
                 cond,
                 tail,
                 content,
+                label,
             } => {
                 let crr_label = *label_counter + 1;
-                *label_counter = *label_counter + 1;
+                *label_counter += 1;
                 if let Some(init) = init {
-                    init.to_assembly(out, label_counter);
+                    init.to_assembly(out, label_counter)?;
+                    out.push(Pop(Rax));
                 }
+
                 out.push(Label("begin", crr_label));
                 if let Some(cond) = cond {
-                    cond.to_assembly(out, label_counter);
-                    out.push(Pop(Rax));
-                    out.push(Cmp(Rax, Num(0)));
+                    cond.to_assembly(out, label_counter)?;
+                    pop_and_test(out, &cond.get_type());
                     out.push(Je("end", crr_label));
                 }
-                content.to_assembly(out, label_counter);
+                content.to_assembly(out, label_counter)?;
+                out.push(Label("continue", *label));
                 if let Some(tail) = tail {
-                    tail.to_assembly(out, label_counter);
+                    tail.to_assembly(out, label_counter)?;
+                    out.push(Pop(Rax));
                 }
                 out.push(Jmp("begin", crr_label));
                 out.push(Label("end", crr_label));
+                out.push(Label("break", *label));
+            }
+            Stmt::DoWhile {
+                content,
+                cond,
+                label,
+            } => {
+                let crr_label = *label_counter + 1;
+                *label_counter += 1;
+                out.push(Label("begin", crr_label));
+                content.to_assembly(out, label_counter)?;
+                out.push(Label("continue", *label));
+                cond.to_assembly(out, label_counter)?;
+                pop_and_test(out, &cond.get_type());
+                out.push(Je("end", crr_label));
+                out.push(Jmp("begin", crr_label));
+                out.push(Label("end", crr_label));
+                out.push(Label("break", *label));
+            }
+            Stmt::Switch {
+                cond,
+                content,
+                cases,
+                default,
+                label,
+            } => {
+                cond.to_assembly(out, label_counter)?;
+                out.push(Pop(Rax));
+                let otherwise = match default {
+                    Some(id) => ("case", *id),
+                    None => ("break", *label),
+                };
+                let min = cases.iter().map(|x| x.0).min().unwrap_or(0);
+                let max = cases.iter().map(|x| x.0).max().unwrap_or(0);
+                // 値が密に詰まっていればジャンプテーブルで飛ぶ
+                if cases.len() >= 4 && max - min < cases.len() as i64 * 3 {
+                    out.push(Sub(Rax, Num(min as i32)));
+                    out.push(Cmp(Rax, Num((max - min) as i32)));
+                    out.push(Ja(otherwise.0, otherwise.1));
+                    out.push(Imul(Rax, Num(8)));
+                    out.push(Lea(Rdi, format!(".Ltable{}", label)));
+                    out.push(Add(Rax, Rdi));
+                    out.push(Load(Rax, Rax));
+                    out.push(Add(Rax, Rdi));
+                    out.push(JmpReg(Rax));
+                    out.push(Align(8));
+                    out.push(Label("table", *label));
+                    for v in min..=max {
+                        // 飛び先がないところは default か switch の外
+                        let (s, n) = match cases.iter().find(|x| x.0 == v) {
+                            Some(&(_, id)) => ("case", id),
+                            None => otherwise,
+                        };
+                        out.push(TableEntry(s, n, *label));
+                    }
+                } else {
+                    for (v, id) in cases {
+                        out.push(Cmp(Rax, Num(*v as i32)));
+                        out.push(Je("case", *id));
+                    }
+                    out.push(Jmp(otherwise.0, otherwise.1));
+                }
+                content.to_assembly(out, label_counter)?;
+                out.push(Label("break", *label));
+            }
+            Stmt::Case(id, content) => {
+                out.push(Label("case", *id));
+                content.to_assembly(out, label_counter)?;
+            }
+            Stmt::Break(label) => out.push(Jmp("break", *label)),
+
+            Stmt::Continue(label) => out.push(Jmp("continue", *label)),
+
+            Stmt::Labeled(id, content) => {
+                out.push(Label("label", *id));
+                content.to_assembly(out, label_counter)?;
             }
+            Stmt::Goto(id) => out.push(Jmp("label", *id)),
+
             Stmt::Calc { content } => {
-                content.to_assembly(out, label_counter);
+                content.to_assembly(out, label_counter)?;
                 out.push(Pop(Rax));
             }
             Stmt::Declare => {}
         }
+        Ok(())
+    }
+}
+
+impl GenAssembly for Program {
+    fn to_assembly(
+        &self,
+        out: &mut Vec<Operation>,
+        label_counter: &mut usize,
+    ) -> Result<(), CompileError> {
+        use crate::binary::Operation::*;
+        use crate::binary::Section as S;
+        for f in &self.funcs {
+            f.to_assembly(out, label_counter)?;
+        }
+        out.push(Section(S::Rodata));
+        for (i, s) in self.strings.iter().enumerate() {
+            out.push(Label("str", i));
+            out.push(Bytes(s.clone()));
+        }
+        for g in &self.globals {
+            let size = g.type_name.size();
+            match &g.init {
+                Some(init) => {
+                    out.push(Section(S::Data));
+                    out.push(Align(g.type_name.align()));
+                    out.push(Global(g.name.clone()));
+                    match init {
+                        GlobalInit::Value(v) => out.push(Bytes(v.to_le_bytes()[..size].to_vec())),
+                        GlobalInit::Address(name, offset) => out.push(Quad(name.clone(), *offset)),
+                    }
+                }
+                None => {
+                    out.push(Section(S::Bss));
+                    out.push(Align(g.type_name.align()));
+                    out.push(Global(g.name.clone()));
+                    out.push(Zero(size));
+                }
+            }
+        }
+        Ok(())
     }
 }
 
 impl GenAssembly for FuncDef {
-    fn to_assembly(&self, out: &mut Vec<Operation>, label_counter: &mut usize) -> () {
+    fn to_assembly(
+        &self,
+        out: &mut Vec<Operation>,
+        label_counter: &mut usize,
+    ) -> Result<(), CompileError> {
         use crate::binary::Operation::*;
         use crate::binary::RegisterOrNum::*;
+        let (fargs, iargs): (Vec<&ValInfo>, Vec<&ValInfo>) =
+            self.args.iter().partition(|x| x.type_name.is_float());
+        let regs: usize = iargs.iter().map(|x| eightbytes(&x.type_name)).sum();
+        if regs > ARG_REGS.len() {
+            return Err(CompileError::new(
+                self.span,
+                format!(
+                    "too many parameters: at most 6 registers are supported, {} needed",
+                    regs
+                ),
+            ));
+        }
+        if fargs.len() > 8 {
+            return Err(CompileError::new(
+                self.span,
+                format!(
+                    "too many floating-point parameters: at most 8 registers are supported, {} needed",
+                    fargs.len()
+                ),
+            ));
+        }
         out.push(Func(self.name.clone()));
         out.push(Push(Rbp));
         out.push(Mov(Rbp, Rsp));
         out.push(Sub(Rsp, Num(self.local_area as i32)));
-        // 関数の引数をスタックにコピーする
-        let arg_regi = vec![Rdi, Rsi, Rdx, Rcx, R8, R9];
-        out.push(Mov(Rax, Rbp));
-        let args_num = self.args.len();
-        assert!(args_num <= 6, "関数の引数は6つまででです。");
-        for i in 0..args_num {
-            out.push(Sub(Rax, Num(8)));
-            out.push(Store(Rax, arg_regi[i].clone()));
+        // 関数の引数をスタックにコピーする. 構造体は複数のレジスタにまたがる
+        let mut regs = ARG_REGS.iter();
+        for info in iargs {
+            for i in 0..eightbytes(&info.type_name) {
+                out.push(Mov(Rax, Rbp));
+                out.push(Sub(Rax, Num((info.offset - i * 8) as i32)));
+                out.push(Store(Rax, regs.next().unwrap().clone()));
+            }
         }
+        // rdi を使うので汎用レジスタの引数を書き出してから
+        for (i, info) in fargs.into_iter().enumerate() {
+            out.push(Mov(Rax, Rbp));
+            out.push(Sub(Rax, Num(info.offset as i32)));
+            out.push(Movq(Rdi, Xmm(i as u8)));
+            out.push(Store(Rax, Rdi));
+        }
+
         for i in &self.body {
-            i.to_assembly(out, label_counter);
+            i.to_assembly(out, label_counter)?;
         }
         out.push(Mov(Rsp, Rbp));
         out.push(Pop(Rbp));
         out.push(Ret);
+        Ok(())
     }
 }
@@ -1,46 +1,143 @@
 use std::fmt;
-use std::fs::File;
 use std::io::Write;
-use std::path::Path;
+
+mod elf;
+mod encode;
 
 #[derive(Clone)]
 pub enum RegisterOrNum {
     Rdi,
     Rdx,
     Rax,
+    Eax,
+    Ax,
+    Edi,
+    Di,
     Rbp,
     Rsp,
     Al,
+    Dil,
+    Dl,
+    Cl,
     Rsi,
     Rcx,
     R8,
     R9,
+    R10,
+    /// SSE レジスタ xmm0 から xmm7
+    Xmm(u8),
     Num(i32),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Section {
+    Text,
+    Rodata,
+    Data,
+    Bss,
+}
+
 pub enum Operation {
     Push(RegisterOrNum),
     Pop(RegisterOrNum),
     Add(RegisterOrNum, RegisterOrNum),
     Sub(RegisterOrNum, RegisterOrNum),
+    And(RegisterOrNum, RegisterOrNum),
+    Or(RegisterOrNum, RegisterOrNum),
+    Xor(RegisterOrNum, RegisterOrNum),
+    Not(RegisterOrNum),
+    /// 2つ目のオペランドは cl だけ
+    Sal(RegisterOrNum, RegisterOrNum),
+    Sar(RegisterOrNum, RegisterOrNum),
+    Shr(RegisterOrNum, RegisterOrNum),
     Imul(RegisterOrNum, RegisterOrNum),
     Cqo,
     Idiv(RegisterOrNum),
+    Div(RegisterOrNum),
     Cmp(RegisterOrNum, RegisterOrNum),
     Sete(RegisterOrNum),
     Setne(RegisterOrNum),
     Setl(RegisterOrNum),
     Setle(RegisterOrNum),
+    /// 符号なしの <, <=
+    Setb(RegisterOrNum),
+    Setbe(RegisterOrNum),
+    /// 符号なしの >, >=
+    Seta(RegisterOrNum),
+    Setae(RegisterOrNum),
+    /// PF を見る. 浮動小数点数の比較で NaN があったかどうか
+    Setp(RegisterOrNum),
+    Setnp(RegisterOrNum),
     Mov(RegisterOrNum, RegisterOrNum),
+    /// 汎用レジスタと xmm レジスタの間で 64 ビットを写す
+    Movq(RegisterOrNum, RegisterOrNum),
+    /// 浮動小数点数の演算. sd は double, ss は float
+    Addsd(RegisterOrNum, RegisterOrNum),
+    Subsd(RegisterOrNum, RegisterOrNum),
+    Mulsd(RegisterOrNum, RegisterOrNum),
+    Divsd(RegisterOrNum, RegisterOrNum),
+    Addss(RegisterOrNum, RegisterOrNum),
+    Subss(RegisterOrNum, RegisterOrNum),
+    Mulss(RegisterOrNum, RegisterOrNum),
+    Divss(RegisterOrNum, RegisterOrNum),
+    /// 浮動小数点数の比較. フラグは符号なし整数の比較と同じように立つ
+    Ucomisd(RegisterOrNum, RegisterOrNum),
+    Ucomiss(RegisterOrNum, RegisterOrNum),
+    /// 64 ビット整数と浮動小数点数の変換. 小数点以下は切り捨てる
+    Cvtsi2sd(RegisterOrNum, RegisterOrNum),
+    Cvtsi2ss(RegisterOrNum, RegisterOrNum),
+    Cvttsd2si(RegisterOrNum, RegisterOrNum),
+    Cvttss2si(RegisterOrNum, RegisterOrNum),
+    Cvtss2sd(RegisterOrNum, RegisterOrNum),
+    Cvtsd2ss(RegisterOrNum, RegisterOrNum),
+    /// 64 ビットの即値を入れる
+    Movabs(RegisterOrNum, i64),
     Load(RegisterOrNum, RegisterOrNum),
     Store(RegisterOrNum, RegisterOrNum),
+    LoadByte(RegisterOrNum, RegisterOrNum),
     Movzb(RegisterOrNum, RegisterOrNum),
+    /// 2つ目のオペランドの幅から符号拡張する. 4 バイトなら movsxd
+    Movsx(RegisterOrNum, RegisterOrNum),
+    Movzx(RegisterOrNum, RegisterOrNum),
+    /// RIP 相対でラベルのアドレスを得る
+    Lea(RegisterOrNum, String),
+    /// GOT からシンボルのアドレスを読む. 同じ実行ファイル内のシンボルならリンカが lea に直す
+    LoadGot(RegisterOrNum, String),
     Ret,
     Je(&'static str, usize),
     Jmp(&'static str, usize),
+    /// 符号なしで大きければ飛ぶ
+    Ja(&'static str, usize),
+    /// 負なら飛ぶ
+    Js(&'static str, usize),
+    /// レジスタの値の番地へ飛ぶ
+    JmpReg(RegisterOrNum),
+    /// switch のジャンプテーブルの要素. 飛び先のラベルの `.Ltable{2}` からの距離
+    TableEntry(&'static str, usize, usize),
     Label(&'static str, usize),
     Func(String),
     Call(String),
+    /// レジスタの値の番地にある関数を呼ぶ
+    CallReg(RegisterOrNum),
+    /// 外部から参照できるデータのラベル
+    Global(String),
+    Section(Section),
+    Align(usize),
+    Bytes(Vec<u8>),
+    /// シンボルのアドレスにバイト数を足した 8 バイトの値
+    Quad(String, i64),
+    Zero(usize),
+}
+
+impl Section {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Section::Text => ".text",
+            Section::Rodata => ".rodata",
+            Section::Data => ".data",
+            Section::Bss => ".bss",
+        }
+    }
 }
 
 impl fmt::Display for RegisterOrNum {
@@ -49,13 +146,22 @@ impl fmt::Display for RegisterOrNum {
             Self::Rdi => write!(f, "rdi"),
             Self::Rdx => write!(f, "rdx"),
             Self::Rax => write!(f, "rax"),
+            Self::Eax => write!(f, "eax"),
+            Self::Ax => write!(f, "ax"),
+            Self::Edi => write!(f, "edi"),
+            Self::Di => write!(f, "di"),
             Self::Rbp => write!(f, "rbp"),
             Self::Rsp => write!(f, "rsp"),
             Self::Al => write!(f, "al"),
+            Self::Dil => write!(f, "dil"),
+            Self::Dl => write!(f, "dl"),
+            Self::Cl => write!(f, "cl"),
             Self::Rsi => write!(f, "rsi"),
             Self::Rcx => write!(f, "rcx"),
             Self::R8 => write!(f, "r8"),
             Self::R9 => write!(f, "r9"),
+            Self::R10 => write!(f, "r10"),
+            Self::Xmm(n) => write!(f, "xmm{}", n),
             Self::Num(n) => write!(f, "{}", n),
         }
     }
@@ -68,38 +174,98 @@ impl fmt::Display for Operation {
             Self::Pop(r) => write!(f, "  pop {}", r),
             Self::Add(r1, r2) => write!(f, "  add {}, {}", r1, r2),
             Self::Sub(r1, r2) => write!(f, "  sub {}, {}", r1, r2),
+            Self::And(r1, r2) => write!(f, "  and {}, {}", r1, r2),
+            Self::Or(r1, r2) => write!(f, "  or {}, {}", r1, r2),
+            Self::Xor(r1, r2) => write!(f, "  xor {}, {}", r1, r2),
+            Self::Not(r) => write!(f, "  not {}", r),
+            Self::Sal(r1, r2) => write!(f, "  sal {}, {}", r1, r2),
+            Self::Sar(r1, r2) => write!(f, "  sar {}, {}", r1, r2),
+            Self::Shr(r1, r2) => write!(f, "  shr {}, {}", r1, r2),
+
             Self::Imul(r1, r2) => write!(f, "  imul {}, {}", r1, r2),
             Self::Cqo => write!(f, "  cqo"),
             Self::Idiv(r) => write!(f, "  idiv {}", r),
+            Self::Div(r) => write!(f, "  div {}", r),
             Self::Cmp(r1, r2) => write!(f, "  cmp {}, {}", r1, r2),
             Self::Sete(r) => write!(f, "  sete {}", r),
             Self::Setne(r) => write!(f, "  setne {}", r),
             Self::Setl(r) => write!(f, "  setl {}", r),
             Self::Setle(r) => write!(f, "  setle {}", r),
+            Self::Setb(r) => write!(f, "  setb {}", r),
+            Self::Setbe(r) => write!(f, "  setbe {}", r),
+            Self::Seta(r) => write!(f, "  seta {}", r),
+            Self::Setae(r) => write!(f, "  setae {}", r),
+            Self::Setp(r) => write!(f, "  setp {}", r),
+            Self::Setnp(r) => write!(f, "  setnp {}", r),
             Self::Mov(r1, r2) => write!(f, "  mov {}, {}", r1, r2),
+            Self::Movabs(r, n) => write!(f, "  movabs {}, {}", r, n),
+            Self::Movq(r1, r2) => write!(f, "  movq {}, {}", r1, r2),
+            Self::Addsd(r1, r2) => write!(f, "  addsd {}, {}", r1, r2),
+            Self::Subsd(r1, r2) => write!(f, "  subsd {}, {}", r1, r2),
+            Self::Mulsd(r1, r2) => write!(f, "  mulsd {}, {}", r1, r2),
+            Self::Divsd(r1, r2) => write!(f, "  divsd {}, {}", r1, r2),
+            Self::Addss(r1, r2) => write!(f, "  addss {}, {}", r1, r2),
+            Self::Subss(r1, r2) => write!(f, "  subss {}, {}", r1, r2),
+            Self::Mulss(r1, r2) => write!(f, "  mulss {}, {}", r1, r2),
+            Self::Divss(r1, r2) => write!(f, "  divss {}, {}", r1, r2),
+            Self::Ucomisd(r1, r2) => write!(f, "  ucomisd {}, {}", r1, r2),
+            Self::Ucomiss(r1, r2) => write!(f, "  ucomiss {}, {}", r1, r2),
+            Self::Cvtsi2sd(r1, r2) => write!(f, "  cvtsi2sd {}, {}", r1, r2),
+            Self::Cvtsi2ss(r1, r2) => write!(f, "  cvtsi2ss {}, {}", r1, r2),
+            Self::Cvttsd2si(r1, r2) => write!(f, "  cvttsd2si {}, {}", r1, r2),
+            Self::Cvttss2si(r1, r2) => write!(f, "  cvttss2si {}, {}", r1, r2),
+            Self::Cvtss2sd(r1, r2) => write!(f, "  cvtss2sd {}, {}", r1, r2),
+            Self::Cvtsd2ss(r1, r2) => write!(f, "  cvtsd2ss {}, {}", r1, r2),
             Self::Load(r1, r2) => write!(f, "  mov {}, [{}]", r1, r2),
             Self::Store(r1, r2) => write!(f, "  mov [{}], {}", r1, r2),
+            Self::LoadByte(r1, r2) => write!(f, "  movsx {}, byte ptr [{}]", r1, r2),
             Self::Movzb(r1, r2) => write!(f, "  movzb {}, {}", r1, r2),
+            Self::Movsx(r1, r2 @ (RegisterOrNum::Eax | RegisterOrNum::Edi)) => {
+                write!(f, "  movsxd {}, {}", r1, r2)
+            }
+            Self::Movsx(r1, r2) => write!(f, "  movsx {}, {}", r1, r2),
+            Self::Movzx(r1, r2) => write!(f, "  movzx {}, {}", r1, r2),
+            Self::Lea(r, label) => write!(f, "  lea {}, [rip + {}]", r, label),
+            Self::LoadGot(r, name) => write!(f, "  mov {}, [rip + {}@GOTPCREL]", r, name),
             Self::Ret => write!(f, "  ret"),
             Self::Je(s, n) => write!(f, "  je .L{}{}", s, n),
             Self::Jmp(s, n) => write!(f, "  jmp .L{}{}", s, n),
+            Self::Ja(s, n) => write!(f, "  ja .L{}{}", s, n),
+            Self::Js(s, n) => write!(f, "  js .L{}{}", s, n),
+            Self::JmpReg(r) => write!(f, "  jmp {}", r),
+            Self::TableEntry(s, n, table) => write!(f, "  .quad .L{}{}-.Ltable{}", s, n, table),
+
             Self::Label(s, n) => write!(f, ".L{}{}:", s, n),
             Self::Func(n) => write!(f, "{}:", n),
             Self::Call(name) => write!(f, "  call {}", name),
+            Self::CallReg(r) => write!(f, "  call {}", r),
+            Self::Global(name) => write!(f, "{}:", name),
+            Self::Section(s) => write!(f, ".section {}", s.name()),
+            Self::Align(n) => write!(f, "  .balign {}", n),
+            Self::Zero(n) => write!(f, "  .zero {}", n),
+            Self::Quad(name, offset) => write!(f, "  .quad {}{:+}", name, offset),
+            Self::Bytes(b) => {
+                let b = b.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+                write!(f, "  .byte {}", b.join(","))
+            }
         }
     }
 }
 
-pub fn elf_writer(path: &str, oprations: &Vec<Operation>) -> std::io::Result<()> {
-    let path = Path::new(path);
-    let mut file = match File::create(&path) {
-        Err(why) => panic!("couldn't open {}: {}", path.display(), why),
-        Ok(file) => file,
-    };
+/// Intel 記法のアセンブリを書き出す
+pub fn asm_writer(file: &mut impl Write, oprations: &[Operation]) -> std::io::Result<()> {
     file.write_all(b".intel_syntax noprefix\n")?;
-    file.write_all(b".globl main\n")?;
     for i in oprations {
-        write!(file, "{}\n", i)?;
+        if let Operation::Func(name) | Operation::Global(name) = i {
+            writeln!(file, ".globl {}", name)?;
+        }
+        writeln!(file, "{}", i)?;
     }
-    file.write_all(b"\n")
+    file.write_all(b".section .note.GNU-stack,\"\",@progbits\n")
+}
+
+/// 再配置可能な ELF オブジェクトファイルを書き出す
+pub fn elf_writer(file: &mut impl Write, oprations: &[Operation]) -> std::io::Result<()> {
+    let obj = encode::assemble(oprations);
+    file.write_all(&elf::build(&obj))
 }
@@ -0,0 +1,279 @@
+use super::encode::{Object, RelocTarget};
+use super::Section;
+
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+const SHT_RELA: u32 = 4;
+const SHT_NOBITS: u32 = 8;
+
+const SHF_WRITE: u64 = 0x1;
+const SHF_ALLOC: u64 = 0x2;
+const SHF_EXECINSTR: u64 = 0x4;
+const SHF_INFO_LINK: u64 = 0x40;
+
+const STB_LOCAL: u8 = 0;
+const STB_GLOBAL: u8 = 1;
+const STT_NOTYPE: u8 = 0;
+const STT_OBJECT: u8 = 1;
+const STT_FUNC: u8 = 2;
+const STT_SECTION: u8 = 3;
+
+struct StrTab {
+    data: Vec<u8>,
+}
+
+impl StrTab {
+    fn new() -> StrTab {
+        StrTab { data: vec![0] }
+    }
+
+    fn add(&mut self, s: &str) -> u32 {
+        if s.is_empty() {
+            return 0;
+        }
+        let pos = self.data.len() as u32;
+        self.data.extend_from_slice(s.as_bytes());
+        self.data.push(0);
+        pos
+    }
+}
+
+struct SectionHeader {
+    name: u32,
+    sh_type: u32,
+    flags: u64,
+    offset: u64,
+    size: u64,
+    link: u32,
+    info: u32,
+    align: u64,
+    entsize: u64,
+}
+
+impl SectionHeader {
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.name.to_le_bytes());
+        out.extend_from_slice(&self.sh_type.to_le_bytes());
+        out.extend_from_slice(&self.flags.to_le_bytes());
+        out.extend_from_slice(&0u64.to_le_bytes()); // sh_addr
+        out.extend_from_slice(&self.offset.to_le_bytes());
+        out.extend_from_slice(&self.size.to_le_bytes());
+        out.extend_from_slice(&self.link.to_le_bytes());
+        out.extend_from_slice(&self.info.to_le_bytes());
+        out.extend_from_slice(&self.align.to_le_bytes());
+        out.extend_from_slice(&self.entsize.to_le_bytes());
+    }
+}
+
+fn write_symbol(out: &mut Vec<u8>, name: u32, info: u8, shndx: u16, value: u64) {
+    out.extend_from_slice(&name.to_le_bytes());
+    out.push(info);
+    out.push(0); // st_other
+    out.extend_from_slice(&shndx.to_le_bytes());
+    out.extend_from_slice(&value.to_le_bytes());
+    out.extend_from_slice(&0u64.to_le_bytes()); // st_size
+}
+
+fn align_to(out: &mut Vec<u8>, align: usize) {
+    while !out.len().is_multiple_of(align) {
+        out.push(0);
+    }
+}
+
+/// 再配置可能な ELF64 オブジェクトファイルのバイト列を作る
+pub fn build(obj: &Object) -> Vec<u8> {
+    // セクションヘッダの並び:
+    // null, 各セクション, 各 .rela, .symtab, .strtab, .shstrtab, .note.GNU-stack
+    let section_num = obj.sections.len();
+    let rela_targets: Vec<usize> = (0..section_num)
+        .filter(|&i| !obj.sections[i].relocs.is_empty())
+        .collect();
+    let symtab_index = 1 + section_num + rela_targets.len();
+    let strtab_index = symtab_index + 1;
+    let shstrtab_index = strtab_index + 1;
+
+    let mut strtab = StrTab::new();
+    let mut shstrtab = StrTab::new();
+
+    // シンボルテーブル: null, セクションシンボル, グローバルの順.
+    // ファイル内のラベルはセクションからの位置で参照するのでシンボルにしない
+    let mut symtab = vec![];
+    write_symbol(&mut symtab, 0, 0, 0, 0);
+    for i in 0..section_num {
+        write_symbol(
+            &mut symtab,
+            0,
+            (STB_LOCAL << 4) | STT_SECTION,
+            (i + 1) as u16,
+            0,
+        );
+    }
+    let first_global = 1 + section_num;
+    let mut symbol_index = vec![0; obj.symbols.len()];
+    for (i, sym) in obj.symbols.iter().enumerate() {
+        let (shndx, value, ty) = match sym.section {
+            Some(s) => {
+                let ty = match obj.sections[s].kind {
+                    Section::Text => STT_FUNC,
+                    _ => STT_OBJECT,
+                };
+                ((s + 1) as u16, sym.offset as u64, ty)
+            }
+            None => (0, 0, STT_NOTYPE),
+        };
+        write_symbol(
+            &mut symtab,
+            strtab.add(&sym.name),
+            (STB_GLOBAL << 4) | ty,
+            shndx,
+            value,
+        );
+        symbol_index[i] = first_global + i;
+    }
+
+    let mut out = vec![0u8; 64];
+    let mut headers = vec![SectionHeader {
+        name: 0,
+        sh_type: 0,
+        flags: 0,
+        offset: 0,
+        size: 0,
+        link: 0,
+        info: 0,
+        align: 0,
+        entsize: 0,
+    }];
+
+    for sec in &obj.sections {
+        let (sh_type, flags) = match sec.kind {
+            Section::Text => (SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR),
+            Section::Rodata => (SHT_PROGBITS, SHF_ALLOC),
+            Section::Data => (SHT_PROGBITS, SHF_ALLOC | SHF_WRITE),
+            Section::Bss => (SHT_NOBITS, SHF_ALLOC | SHF_WRITE),
+        };
+        align_to(&mut out, sec.align);
+        let offset = out.len() as u64;
+        // .bss はファイル中に中身を持たない
+        if sh_type != SHT_NOBITS {
+            out.extend_from_slice(&sec.data);
+        }
+        headers.push(SectionHeader {
+            name: shstrtab.add(sec.kind.name()),
+            sh_type,
+            flags,
+            offset,
+            size: sec.data.len() as u64,
+            link: 0,
+            info: 0,
+            align: sec.align as u64,
+            entsize: 0,
+        });
+    }
+
+    for &i in &rela_targets {
+        align_to(&mut out, 8);
+        let offset = out.len() as u64;
+        for r in &obj.sections[i].relocs {
+            let sym = match r.target {
+                RelocTarget::Section(s) => 1 + s,
+                RelocTarget::Symbol(s) => symbol_index[s],
+            } as u64;
+            out.extend_from_slice(&(r.offset as u64).to_le_bytes());
+            out.extend_from_slice(&((sym << 32) | r.kind as u64).to_le_bytes());
+            out.extend_from_slice(&r.addend.to_le_bytes());
+        }
+        let name = format!(".rela{}", obj.sections[i].kind.name());
+        headers.push(SectionHeader {
+            name: shstrtab.add(&name),
+            sh_type: SHT_RELA,
+            flags: SHF_INFO_LINK,
+            offset,
+            size: out.len() as u64 - offset,
+            link: symtab_index as u32,
+            info: (i + 1) as u32,
+            align: 8,
+            entsize: 24,
+        });
+    }
+
+    align_to(&mut out, 8);
+    headers.push(SectionHeader {
+        name: shstrtab.add(".symtab"),
+        sh_type: SHT_SYMTAB,
+        flags: 0,
+        offset: out.len() as u64,
+        size: symtab.len() as u64,
+        link: strtab_index as u32,
+        info: first_global as u32,
+        align: 8,
+        entsize: 24,
+    });
+    out.extend_from_slice(&symtab);
+
+    headers.push(SectionHeader {
+        name: shstrtab.add(".strtab"),
+        sh_type: SHT_STRTAB,
+        flags: 0,
+        offset: out.len() as u64,
+        size: strtab.data.len() as u64,
+        link: 0,
+        info: 0,
+        align: 1,
+        entsize: 0,
+    });
+    out.extend_from_slice(&strtab.data);
+
+    let shstrtab_name = shstrtab.add(".shstrtab");
+    let note_name = shstrtab.add(".note.GNU-stack");
+    headers.push(SectionHeader {
+        name: shstrtab_name,
+        sh_type: SHT_STRTAB,
+        flags: 0,
+        offset: out.len() as u64,
+        size: shstrtab.data.len() as u64,
+        link: 0,
+        info: 0,
+        align: 1,
+        entsize: 0,
+    });
+    out.extend_from_slice(&shstrtab.data);
+
+    // スタックを実行不可にするための空セクション
+    headers.push(SectionHeader {
+        name: note_name,
+        sh_type: SHT_PROGBITS,
+        flags: 0,
+        offset: out.len() as u64,
+        size: 0,
+        link: 0,
+        info: 0,
+        align: 1,
+        entsize: 0,
+    });
+
+    align_to(&mut out, 8);
+    let shoff = out.len() as u64;
+    for h in &headers {
+        h.write(&mut out);
+    }
+
+    // ELF ヘッダ
+    let mut header = vec![0x7f, b'E', b'L', b'F', 2, 1, 1, 0];
+    header.resize(16, 0);
+    header.extend_from_slice(&1u16.to_le_bytes()); // ET_REL
+    header.extend_from_slice(&62u16.to_le_bytes()); // EM_X86_64
+    header.extend_from_slice(&1u32.to_le_bytes()); // EV_CURRENT
+    header.extend_from_slice(&0u64.to_le_bytes()); // e_entry
+    header.extend_from_slice(&0u64.to_le_bytes()); // e_phoff
+    header.extend_from_slice(&shoff.to_le_bytes());
+    header.extend_from_slice(&0u32.to_le_bytes()); // e_flags
+    header.extend_from_slice(&64u16.to_le_bytes()); // e_ehsize
+    header.extend_from_slice(&0u16.to_le_bytes()); // e_phentsize
+    header.extend_from_slice(&0u16.to_le_bytes()); // e_phnum
+    header.extend_from_slice(&64u16.to_le_bytes()); // e_shentsize
+    header.extend_from_slice(&(headers.len() as u16).to_le_bytes());
+    header.extend_from_slice(&(shstrtab_index as u16).to_le_bytes());
+    out[..64].copy_from_slice(&header);
+    out
+}
@@ -0,0 +1,543 @@
+use super::{Operation, RegisterOrNum, Section};
+use std::collections::HashMap;
+
+const R_X86_64_64: u32 = 1;
+const R_X86_64_PC32: u32 = 2;
+const R_X86_64_PLT32: u32 = 4;
+const R_X86_64_REX_GOTPCRELX: u32 = 42;
+
+/// 出力するセクションの並び
+const SECTIONS: [Section; 4] = [Section::Text, Section::Rodata, Section::Data, Section::Bss];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RelocTarget {
+    /// セクションの先頭 (ローカルラベルの参照に使う)
+    Section(usize),
+    Symbol(usize),
+}
+
+pub struct Reloc {
+    pub offset: usize,
+    pub target: RelocTarget,
+    pub kind: u32,
+    pub addend: i64,
+}
+
+pub struct SectionData {
+    pub kind: Section,
+    pub align: usize,
+    pub data: Vec<u8>,
+    pub relocs: Vec<Reloc>,
+}
+
+/// 関数と大域変数のシンボル. すべてグローバルにする
+pub struct Symbol {
+    pub name: String,
+    /// 未定義シンボルは `None`
+    pub section: Option<usize>,
+    pub offset: usize,
+}
+
+pub struct Object {
+    pub sections: Vec<SectionData>,
+    pub symbols: Vec<Symbol>,
+}
+
+/// ModR/M の r/m 側のオペランド
+enum Rm {
+    Reg(u8),
+    /// `[base]`
+    Mem(u8),
+    /// `[rip + disp32]`
+    Rip,
+}
+
+impl RegisterOrNum {
+    fn code(&self) -> u8 {
+        use RegisterOrNum::*;
+        match self {
+            Rax | Eax | Ax | Al => 0,
+            Rcx | Cl => 1,
+            Rdx | Dl => 2,
+            Rsp => 4,
+            Rbp => 5,
+            Rsi => 6,
+            Rdi | Edi | Di | Dil => 7,
+            R8 => 8,
+            R9 => 9,
+            R10 => 10,
+            Xmm(n) => *n,
+            Num(n) => panic!("immediate {} is not a register", n),
+        }
+    }
+
+    /// オペランドのバイト幅
+    fn size(&self) -> u8 {
+        match self {
+            RegisterOrNum::Al | RegisterOrNum::Dil | RegisterOrNum::Dl | RegisterOrNum::Cl => 1,
+            RegisterOrNum::Ax | RegisterOrNum::Di => 2,
+            RegisterOrNum::Eax | RegisterOrNum::Edi => 4,
+            _ => 8,
+        }
+    }
+
+    /// spl, bpl, sil, dil を使うには REX プレフィックスが必要
+    fn needs_rex(&self) -> bool {
+        self.size() == 1 && (4..8).contains(&self.code())
+    }
+}
+
+/// ラベルかシンボルを参照する位置 (セクション, 位置, 参照先の名前, 再配置の種類と加数)
+struct LabelRef {
+    section: usize,
+    offset: usize,
+    name: String,
+    kind: u32,
+    addend: i64,
+}
+
+struct Assembler {
+    sections: Vec<SectionData>,
+    /// 現在出力しているセクション
+    current: usize,
+    symbols: Vec<Symbol>,
+    symbol_index: HashMap<String, usize>,
+    /// ラベル名とその (セクション, 位置)
+    labels: HashMap<String, (usize, usize)>,
+    /// rel32 を書き込む位置とジャンプ先ラベル
+    fixups: Vec<(usize, String)>,
+    /// ジャンプテーブルの要素を書き込む位置と (飛び先, テーブル先頭) のラベル
+    table_fixups: Vec<(usize, String, String)>,
+    label_refs: Vec<LabelRef>,
+}
+
+impl Assembler {
+    fn new() -> Assembler {
+        Assembler {
+            sections: SECTIONS
+                .iter()
+                .map(|&kind| SectionData {
+                    kind,
+                    align: if kind == Section::Text { 16 } else { 1 },
+                    data: vec![],
+                    relocs: vec![],
+                })
+                .collect(),
+            current: 0,
+            symbols: vec![],
+            symbol_index: HashMap::new(),
+            labels: HashMap::new(),
+            fixups: vec![],
+            table_fixups: vec![],
+            label_refs: vec![],
+        }
+    }
+
+    fn text(&mut self) -> &mut Vec<u8> {
+        &mut self.sections[self.current].data
+    }
+
+    fn pos(&self) -> usize {
+        self.sections[self.current].data.len()
+    }
+
+    fn symbol(&mut self, name: &str) -> usize {
+        if let Some(&i) = self.symbol_index.get(name) {
+            return i;
+        }
+        self.symbols.push(Symbol {
+            name: name.into(),
+            section: None,
+            offset: 0,
+        });
+        self.symbol_index
+            .insert(name.into(), self.symbols.len() - 1);
+        self.symbols.len() - 1
+    }
+
+    fn emit(&mut self, bytes: &[u8]) {
+        self.text().extend_from_slice(bytes);
+    }
+
+    fn emit_i32(&mut self, n: i32) {
+        self.emit(&n.to_le_bytes());
+    }
+
+    /// プレフィックス, REX, オペコード, ModR/M を出力する
+    fn emit_modrm(&mut self, size: u8, force_rex: bool, opcode: &[u8], reg: u8, rm: Rm) {
+        let rm_code = match rm {
+            Rm::Reg(r) | Rm::Mem(r) => r,
+            Rm::Rip => 5,
+        };
+        if size == 2 {
+            self.text().push(0x66);
+        }
+        let mut rex = 0x40;
+        if size == 8 {
+            rex |= 0x08;
+        }
+        if reg & 8 != 0 {
+            rex |= 0x04;
+        }
+        if rm_code & 8 != 0 {
+            rex |= 0x01;
+        }
+        if rex != 0x40 || force_rex {
+            self.text().push(rex);
+        }
+        self.emit(opcode);
+        match rm {
+            Rm::Reg(r) => self.text().push(0xc0 | (reg & 7) << 3 | (r & 7)),
+            Rm::Mem(base) => match base & 7 {
+                // rsp, r12 は SIB バイトが必要
+                4 => self.emit(&[(reg & 7) << 3 | 4, 0x24]),
+                // rbp, r13 は disp8 を付けないと RIP 相対などと区別できない
+                5 => self.emit(&[0x40 | (reg & 7) << 3 | 5, 0]),
+                b => self.text().push((reg & 7) << 3 | b),
+            },
+            Rm::Rip => self.text().push((reg & 7) << 3 | 5),
+        }
+    }
+
+    /// `op r/m, reg` 形式の命令
+    fn reg_reg(&mut self, opcode: u8, dst: &RegisterOrNum, src: &RegisterOrNum) {
+        let opcode = if src.size() == 1 { opcode - 1 } else { opcode };
+        let force_rex = dst.needs_rex() || src.needs_rex();
+        self.emit_modrm(
+            src.size(),
+            force_rex,
+            &[opcode],
+            src.code(),
+            Rm::Reg(dst.code()),
+        );
+    }
+
+    /// add, sub, cmp などのグループ1命令
+    fn arith(&mut self, opcode: u8, ext: u8, dst: &RegisterOrNum, src: &RegisterOrNum) {
+        match src {
+            RegisterOrNum::Num(n) => {
+                if let Ok(n) = i8::try_from(*n) {
+                    self.emit_modrm(dst.size(), false, &[0x83], ext, Rm::Reg(dst.code()));
+                    self.text().push(n as u8);
+                } else {
+                    self.emit_modrm(dst.size(), false, &[0x81], ext, Rm::Reg(dst.code()));
+                    self.emit_i32(*n);
+                }
+            }
+            _ => self.reg_reg(opcode, dst, src),
+        }
+    }
+
+    /// `0F` で始まる SSE 命令. 必須プレフィックスは REX より前に置く. `wide` なら REX.W を付ける
+    fn sse(
+        &mut self,
+        prefix: &[u8],
+        opcode: u8,
+        wide: bool,
+        reg: &RegisterOrNum,
+        rm: &RegisterOrNum,
+    ) {
+        self.emit(prefix);
+        let size = if wide { 8 } else { 4 };
+        self.emit_modrm(size, false, &[0x0f, opcode], reg.code(), Rm::Reg(rm.code()));
+    }
+
+    fn setcc(&mut self, cc: u8, dst: &RegisterOrNum) {
+        self.emit_modrm(1, dst.needs_rex(), &[0x0f, cc], 0, Rm::Reg(dst.code()));
+    }
+
+    fn jump(&mut self, opcode: &[u8], label: String) {
+        self.emit(opcode);
+        self.fixups.push((self.pos(), label));
+        self.emit_i32(0);
+    }
+
+    fn encode(&mut self, op: &Operation) {
+        use Operation::*;
+        use RegisterOrNum::*;
+        match op {
+            Push(Num(n)) => {
+                if let Ok(n) = i8::try_from(*n) {
+                    self.emit(&[0x6a, n as u8]);
+                } else {
+                    self.text().push(0x68);
+                    self.emit_i32(*n);
+                }
+            }
+            Push(r) => {
+                if r.code() & 8 != 0 {
+                    self.text().push(0x41);
+                }
+                self.text().push(0x50 | (r.code() & 7));
+            }
+            Pop(r) => {
+                if r.code() & 8 != 0 {
+                    self.text().push(0x41);
+                }
+                self.text().push(0x58 | (r.code() & 7));
+            }
+            Add(r1, r2) => self.arith(0x01, 0, r1, r2),
+            Sub(r1, r2) => self.arith(0x29, 5, r1, r2),
+            And(r1, r2) => self.arith(0x21, 4, r1, r2),
+            Or(r1, r2) => self.arith(0x09, 1, r1, r2),
+            Xor(r1, r2) => self.arith(0x31, 6, r1, r2),
+            Not(r) => self.emit_modrm(r.size(), false, &[0xf7], 2, Rm::Reg(r.code())),
+            // シフト量は cl に入っている
+            Sal(r, _) => self.emit_modrm(r.size(), false, &[0xd3], 4, Rm::Reg(r.code())),
+            Sar(r, _) => self.emit_modrm(r.size(), false, &[0xd3], 7, Rm::Reg(r.code())),
+            Shr(r, _) => self.emit_modrm(r.size(), false, &[0xd3], 5, Rm::Reg(r.code())),
+
+            Cmp(r1, r2) => self.arith(0x39, 7, r1, r2),
+            Imul(r1, Num(n)) => {
+                self.emit_modrm(r1.size(), false, &[0x69], r1.code(), Rm::Reg(r1.code()));
+                self.emit_i32(*n);
+            }
+            Imul(r1, r2) => self.emit_modrm(
+                r1.size(),
+                false,
+                &[0x0f, 0xaf],
+                r1.code(),
+                Rm::Reg(r2.code()),
+            ),
+            Cqo => self.emit(&[0x48, 0x99]),
+            Idiv(r) => self.emit_modrm(r.size(), false, &[0xf7], 7, Rm::Reg(r.code())),
+            Div(r) => self.emit_modrm(r.size(), false, &[0xf7], 6, Rm::Reg(r.code())),
+            Sete(r) => self.setcc(0x94, r),
+            Setne(r) => self.setcc(0x95, r),
+            Setl(r) => self.setcc(0x9c, r),
+            Setle(r) => self.setcc(0x9e, r),
+            Setb(r) => self.setcc(0x92, r),
+            Setbe(r) => self.setcc(0x96, r),
+            Seta(r) => self.setcc(0x97, r),
+            Setae(r) => self.setcc(0x93, r),
+            Setp(r) => self.setcc(0x9a, r),
+            Setnp(r) => self.setcc(0x9b, r),
+            Mov(r1, Num(n)) => {
+                self.emit_modrm(r1.size(), false, &[0xc7], 0, Rm::Reg(r1.code()));
+                self.emit_i32(*n);
+            }
+            Mov(r1, r2) => self.reg_reg(0x89, r1, r2),
+            // xmm へは 66 REX.W 0F 6E, xmm からは 66 REX.W 0F 7E
+            Movq(r1 @ Xmm(_), r2) => self.sse(&[0x66], 0x6e, true, r1, r2),
+            Movq(r1, r2) => self.sse(&[0x66], 0x7e, true, r2, r1),
+            Addsd(r1, r2) => self.sse(&[0xf2], 0x58, false, r1, r2),
+            Subsd(r1, r2) => self.sse(&[0xf2], 0x5c, false, r1, r2),
+            Mulsd(r1, r2) => self.sse(&[0xf2], 0x59, false, r1, r2),
+            Divsd(r1, r2) => self.sse(&[0xf2], 0x5e, false, r1, r2),
+            Addss(r1, r2) => self.sse(&[0xf3], 0x58, false, r1, r2),
+            Subss(r1, r2) => self.sse(&[0xf3], 0x5c, false, r1, r2),
+            Mulss(r1, r2) => self.sse(&[0xf3], 0x59, false, r1, r2),
+            Divss(r1, r2) => self.sse(&[0xf3], 0x5e, false, r1, r2),
+            Ucomisd(r1, r2) => self.sse(&[0x66], 0x2e, false, r1, r2),
+            Ucomiss(r1, r2) => self.sse(&[], 0x2e, false, r1, r2),
+            Cvtsi2sd(r1, r2) => self.sse(&[0xf2], 0x2a, true, r1, r2),
+            Cvtsi2ss(r1, r2) => self.sse(&[0xf3], 0x2a, true, r1, r2),
+            Cvttsd2si(r1, r2) => self.sse(&[0xf2], 0x2c, true, r1, r2),
+            Cvttss2si(r1, r2) => self.sse(&[0xf3], 0x2c, true, r1, r2),
+            Cvtss2sd(r1, r2) => self.sse(&[0xf3], 0x5a, false, r1, r2),
+            Cvtsd2ss(r1, r2) => self.sse(&[0xf2], 0x5a, false, r1, r2),
+            Movabs(r, n) => {
+                let rex = if r.code() & 8 != 0 { 0x49 } else { 0x48 };
+                self.emit(&[rex, 0xb8 | (r.code() & 7)]);
+                self.emit(&n.to_le_bytes());
+            }
+            Load(r1, r2) => {
+                let opcode = if r1.size() == 1 { 0x8a } else { 0x8b };
+                self.emit_modrm(
+                    r1.size(),
+                    r1.needs_rex(),
+                    &[opcode],
+                    r1.code(),
+                    Rm::Mem(r2.code()),
+                )
+            }
+            Store(r1, r2) => {
+                let opcode = if r2.size() == 1 { 0x88 } else { 0x89 };
+                self.emit_modrm(
+                    r2.size(),
+                    r2.needs_rex(),
+                    &[opcode],
+                    r2.code(),
+                    Rm::Mem(r1.code()),
+                )
+            }
+            LoadByte(r1, r2) => self.emit_modrm(
+                r1.size(),
+                false,
+                &[0x0f, 0xbe],
+                r1.code(),
+                Rm::Mem(r2.code()),
+            ),
+            Movzb(r1, r2) => self.emit_modrm(
+                r1.size(),
+                r2.needs_rex(),
+                &[0x0f, 0xb6],
+                r1.code(),
+                Rm::Reg(r2.code()),
+            ),
+            Movsx(r1, r2) => {
+                let opcode: &[u8] = match r2.size() {
+                    1 => &[0x0f, 0xbe],
+                    2 => &[0x0f, 0xbf],
+                    _ => &[0x63],
+                };
+                self.emit_modrm(
+                    r1.size(),
+                    r2.needs_rex(),
+                    opcode,
+                    r1.code(),
+                    Rm::Reg(r2.code()),
+                )
+            }
+            Movzx(r1, r2) => {
+                let opcode = if r2.size() == 1 { 0xb6 } else { 0xb7 };
+                self.emit_modrm(
+                    r1.size(),
+                    r2.needs_rex(),
+                    &[0x0f, opcode],
+                    r1.code(),
+                    Rm::Reg(r2.code()),
+                )
+            }
+            Ret => self.text().push(0xc3),
+            Je(s, n) => self.jump(&[0x0f, 0x84], format!(".L{}{}", s, n)),
+            Jmp(s, n) => self.jump(&[0xe9], format!(".L{}{}", s, n)),
+            Ja(s, n) => self.jump(&[0x0f, 0x87], format!(".L{}{}", s, n)),
+            Js(s, n) => self.jump(&[0x0f, 0x88], format!(".L{}{}", s, n)),
+            JmpReg(r) => {
+                if r.code() & 8 != 0 {
+                    self.text().push(0x41);
+                }
+                self.emit(&[0xff, 0xe0 | (r.code() & 7)]);
+            }
+            CallReg(r) => {
+                if r.code() & 8 != 0 {
+                    self.text().push(0x41);
+                }
+                self.emit(&[0xff, 0xd0 | (r.code() & 7)]);
+            }
+            TableEntry(s, n, table) => {
+                let pos = self.pos();
+                self.table_fixups
+                    .push((pos, format!(".L{}{}", s, n), format!(".Ltable{}", table)));
+
+                self.emit(&[0; 8]);
+            }
+            LoadGot(r, name) => {
+                self.emit_modrm(r.size(), false, &[0x8b], r.code(), Rm::Rip);
+                let symbol = self.symbol(name);
+                let offset = self.pos();
+                self.sections[self.current].relocs.push(Reloc {
+                    offset,
+                    target: RelocTarget::Symbol(symbol),
+                    kind: R_X86_64_REX_GOTPCRELX,
+                    addend: -4,
+                });
+                self.emit_i32(0);
+            }
+            Lea(r, label) => {
+                self.emit_modrm(r.size(), false, &[0x8d], r.code(), Rm::Rip);
+                self.label_refs.push(LabelRef {
+                    section: self.current,
+                    offset: self.pos(),
+                    name: label.clone(),
+                    kind: R_X86_64_PC32,
+                    addend: -4,
+                });
+                self.emit_i32(0);
+            }
+            Quad(name, offset) => {
+                self.label_refs.push(LabelRef {
+                    section: self.current,
+                    offset: self.pos(),
+                    name: name.clone(),
+                    kind: R_X86_64_64,
+                    addend: *offset,
+                });
+                self.emit(&[0; 8]);
+            }
+            Label(s, n) => {
+                let pos = (self.current, self.pos());
+                self.labels.insert(format!(".L{}{}", s, n), pos);
+            }
+            Func(name) | Global(name) => {
+                let i = self.symbol(name);
+                self.symbols[i].section = Some(self.current);
+                self.symbols[i].offset = self.pos();
+            }
+            Call(name) => {
+                self.text().push(0xe8);
+                let symbol = self.symbol(name);
+                let offset = self.pos();
+                self.sections[self.current].relocs.push(Reloc {
+                    offset,
+                    target: RelocTarget::Symbol(symbol),
+                    kind: R_X86_64_PLT32,
+                    addend: -4,
+                });
+                self.emit_i32(0);
+            }
+            Section(s) => {
+                self.current = SECTIONS.iter().position(|x| x == s).unwrap();
+            }
+            Align(n) => {
+                let section = &mut self.sections[self.current];
+                section.align = section.align.max(*n);
+                let len = section.data.len().next_multiple_of(*n);
+                section.data.resize(len, 0);
+            }
+            Bytes(b) => self.emit(b),
+            Zero(n) => {
+                let len = self.pos() + n;
+                self.text().resize(len, 0);
+            }
+        }
+    }
+
+    fn finish(mut self) -> Object {
+        for (pos, label) in &self.fixups {
+            let target = match self.labels.get(label) {
+                Some(&(_, t)) => t,
+                None => panic!("label `{}` is not defined", label),
+            };
+            let rel = target as i64 - (*pos as i64 + 4);
+            self.sections[0].data[*pos..*pos + 4].copy_from_slice(&(rel as i32).to_le_bytes());
+        }
+        for (pos, label, base) in &self.table_fixups {
+            let offset = |label: &String| match self.labels.get(label) {
+                Some(&(_, t)) => t as i64,
+                None => panic!("label `{}` is not defined", label),
+            };
+            let rel = offset(label) - offset(base);
+            self.sections[0].data[*pos..*pos + 8].copy_from_slice(&rel.to_le_bytes());
+        }
+
+        for r in std::mem::take(&mut self.label_refs) {
+            let (target, addend) = match self.labels.get(&r.name) {
+                Some(&(section, offset)) => {
+                    (RelocTarget::Section(section), offset as i64 + r.addend)
+                }
+                None => (RelocTarget::Symbol(self.symbol(&r.name)), r.addend),
+            };
+            self.sections[r.section].relocs.push(Reloc {
+                offset: r.offset,
+                target,
+                kind: r.kind,
+                addend,
+            });
+        }
+        Object {
+            sections: self.sections,
+            symbols: self.symbols,
+        }
+    }
+}
+
+/// 命令列を機械語に変換する
+pub fn assemble(operations: &[Operation]) -> Object {
+    let mut asm = Assembler::new();
+    for op in operations {
+        asm.encode(op);
+    }
+    asm.finish()
+}
@@ -0,0 +1,90 @@
+use crate::parse::Rule;
+use pest::error::{Error, InputLocation};
+
+/// ソースコード中のバイト範囲
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+}
+
+impl From<pest::Span<'_>> for Span {
+    fn from(span: pest::Span<'_>) -> Span {
+        Span::new(span.start(), span.end())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug)]
+pub struct CompileError {
+    pub span: Span,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl CompileError {
+    pub fn new(span: impl Into<Span>, message: impl Into<String>) -> CompileError {
+        CompileError {
+            span: span.into(),
+            severity: Severity::Error,
+            message: message.into(),
+        }
+    }
+
+    /// エラー箇所に `^` を付けた報告文を作る
+    pub fn report(&self, name: &str, source: &str) -> String {
+        let start = self.span.start.min(source.len());
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[start..]
+            .find('\n')
+            .map_or(source.len(), |i| start + i);
+        let line_no = source[..start].matches('\n').count() + 1;
+        let line = &source[line_start..line_end];
+        let col = source[line_start..start].chars().count();
+        let width = source[start..self.span.end.clamp(start, line_end)]
+            .chars()
+            .count()
+            .max(1);
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        let pad = " ".repeat(line_no.to_string().len());
+        format!(
+            "{}: {}\n{}--> {}:{}:{}\n{} |\n{} | {}\n{} | {}{}\n",
+            severity,
+            self.message,
+            pad,
+            name,
+            line_no,
+            col + 1,
+            pad,
+            line_no,
+            line,
+            pad,
+            " ".repeat(col),
+            "^".repeat(width)
+        )
+    }
+}
+
+impl From<Error<Rule>> for CompileError {
+    fn from(e: Error<Rule>) -> CompileError {
+        let span = match e.location {
+            InputLocation::Pos(p) => Span::new(p, p),
+            InputLocation::Span((s, t)) => Span::new(s, t),
+        };
+        CompileError::new(span, e.variant.message())
+    }
+}
@@ -1,4 +1,5 @@
 pub mod ast;
 pub mod binary;
+pub mod error;
 pub mod parse;
 pub mod typing;
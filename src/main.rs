@@ -1,15 +1,208 @@
 use rust_9cc::ast::GenAssembly;
-use rust_9cc::binary;
+use rust_9cc::binary::{self, Operation};
+use rust_9cc::error::CompileError;
 use rust_9cc::parse;
-use std::env::args;
+use std::env::{self, args};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{exit, Command};
 
-fn main() {
-    let arg: Vec<String> = args().collect();
-    let ast = parse::source_to_ast(arg.get(1).unwrap()).unwrap();
+const USAGE: &str = "\
+usage: rust-9cc [options] <file>...
+
+options:
+  -o <file>    write output to <file> (`-` for stdout)
+  -S           compile only; emit assembly
+  -c           compile and assemble; emit an object file
+  -h, --help   print this help
+  --version    print version information
+
+`-` as <file> reads the program from stdin.
+Inputs other than `.c` files are passed to the linker.";
+
+#[derive(PartialEq)]
+enum Mode {
+    Asm,
+    Object,
+    Link,
+}
+
+struct Options {
+    mode: Mode,
+    output: Option<String>,
+    inputs: Vec<String>,
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    eprintln!("try `rust-9cc --help` for more information");
+    exit(1);
+}
+
+fn parse_args(args: &[String]) -> Options {
+    let mut opts = Options {
+        mode: Mode::Link,
+        output: None,
+        inputs: vec![],
+    };
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                exit(0);
+            }
+            "--version" => {
+                println!("rust-9cc {}", env!("CARGO_PKG_VERSION"));
+                exit(0);
+            }
+            "-S" => opts.mode = Mode::Asm,
+            "-c" => {
+                if opts.mode != Mode::Asm {
+                    opts.mode = Mode::Object;
+                }
+            }
+            "-o" => match iter.next() {
+                Some(path) => opts.output = Some(path.clone()),
+                None => usage_error("missing filename after `-o`"),
+            },
+            s if s.starts_with("-o") => opts.output = Some(s[2..].into()),
+            s if s.starts_with('-') && s != "-" => usage_error(&format!("unknown option `{}`", s)),
+            s => opts.inputs.push(s.into()),
+        }
+    }
+    if opts.inputs.is_empty() {
+        usage_error("no input files");
+    }
+    opts
+}
+
+fn is_source(path: &str) -> bool {
+    path == "-" || path.ends_with(".c")
+}
+
+fn read_source(path: &str) -> io::Result<String> {
+    if path == "-" {
+        let mut source = String::new();
+        io::stdin().read_to_string(&mut source)?;
+        Ok(source)
+    } else {
+        fs::read_to_string(path)
+    }
+}
+
+fn compile(source: &str) -> Result<Vec<Operation>, CompileError> {
+    let ast = parse::source_to_ast(source)?;
     let mut label_counter = 0;
     let mut operation = vec![];
-    for a in ast {
-        a.to_assembly(&mut operation, &mut label_counter);
+    ast.to_assembly(&mut operation, &mut label_counter)?;
+    Ok(operation)
+}
+
+/// `foo/bar.c` から `bar.s` などの出力ファイル名を作る. 標準入力からなら標準出力へ
+fn default_output(input: &str, ext: &str) -> String {
+    if input == "-" {
+        return input.into();
+    }
+    let stem = Path::new(input).file_stem().unwrap_or_default();
+    Path::new(stem).with_extension(ext).display().to_string()
+}
+
+fn write_output(path: &str, mode: &Mode, operation: &[Operation]) -> io::Result<()> {
+    let mut file: Box<dyn Write> = if path == "-" {
+        Box::new(io::stdout())
+    } else {
+        Box::new(File::create(path)?)
+    };
+    match mode {
+        Mode::Asm => binary::asm_writer(&mut file, operation),
+        Mode::Object | Mode::Link => binary::elf_writer(&mut file, operation),
+    }
+}
+
+fn cleanup(temps: &[PathBuf]) {
+    for t in temps {
+        let _ = fs::remove_file(t);
+    }
+}
+
+fn main() {
+    let arg: Vec<String> = args().collect();
+    let opts = parse_args(&arg[1..]);
+    let sources = opts.inputs.iter().filter(|x| is_source(x)).count();
+    if opts.output.is_some() && opts.mode != Mode::Link && sources > 1 {
+        usage_error("cannot specify `-o` with `-S` or `-c` and multiple files");
+    }
+
+    let mut link_inputs = vec![];
+    let mut temps = vec![];
+    for (i, input) in opts.inputs.iter().enumerate() {
+        if !is_source(input) {
+            if opts.mode == Mode::Link {
+                link_inputs.push(PathBuf::from(input));
+            } else {
+                eprintln!(
+                    "warning: {}: linker input unused because linking is not done",
+                    input
+                );
+            }
+            continue;
+        }
+        let source = match read_source(input) {
+            Ok(source) => source,
+            Err(e) => {
+                cleanup(&temps);
+                eprintln!("error: couldn't read {}: {}", input, e);
+                exit(1);
+            }
+        };
+        let operation = match compile(&source) {
+            Ok(operation) => operation,
+            Err(e) => {
+                cleanup(&temps);
+                let name = if input == "-" { "<stdin>" } else { input };
+                eprint!("{}", e.report(name, &source));
+                exit(1);
+            }
+        };
+        let output = match opts.mode {
+            Mode::Asm => opts.output.clone().unwrap_or(default_output(input, "s")),
+            Mode::Object => opts.output.clone().unwrap_or(default_output(input, "o")),
+            Mode::Link => {
+                let tmp = env::temp_dir().join(format!("rust-9cc-{}-{}.o", std::process::id(), i));
+                temps.push(tmp.clone());
+                link_inputs.push(tmp.clone());
+                tmp.display().to_string()
+            }
+        };
+        if let Err(e) = write_output(&output, &opts.mode, &operation) {
+            cleanup(&temps);
+            eprintln!("error: couldn't write {}: {}", output, e);
+            exit(1);
+        }
+    }
+
+    if opts.mode == Mode::Link {
+        let output = opts.output.unwrap_or("a.out".into());
+        let status = Command::new("cc")
+            .arg("-o")
+            .arg(&output)
+            .args(&link_inputs)
+            // sqrt などを宣言して呼べるように libm もリンクする
+            .arg("-lm")
+            .status();
+        cleanup(&temps);
+        match status {
+            Ok(s) if s.success() => {}
+            Ok(_) => {
+                eprintln!("error: linking {} failed", output);
+                exit(1);
+            }
+            Err(e) => {
+                eprintln!("error: couldn't run the linker `cc`: {}", e);
+                exit(1);
+            }
+        }
     }
-    binary::elf_writer(&arg[2], &operation).unwrap();
 }
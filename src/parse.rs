@@ -1,30 +1,273 @@
 use crate::ast::*;
-
-use crate::typing::{get_type, Type};
+use crate::error::{CompileError, Span};
+use crate::typing::{get_type, usual_arith, Struct, Type};
 
 use pest;
-use pest::error::Error;
-use pest::error::ErrorVariant;
 use pest::Parser;
 use pest_derive::Parser;
 use std::collections::HashMap;
+use std::rc::Rc;
 
 #[derive(Parser)]
 #[grammar = "calc.pest"]
 struct CalcParser;
 
+/// 変数などの通常の名前
+#[derive(Clone)]
+enum Name {
+    Var(ValInfo),
+    /// 列挙定数
+    Const(i32),
+    Typedef(Type),
+}
+
+/// 構造体, 共用体, 列挙型のタグ
+#[derive(Clone)]
+enum Tag {
+    Struct(Rc<Struct>),
+    Enum,
+}
+
+impl Tag {
+    fn kind(&self) -> &'static str {
+        match self {
+            Tag::Struct(s) if s.is_union() => "union",
+            Tag::Struct(_) => "struct",
+            Tag::Enum => "enum",
+        }
+    }
+}
+
+/// ブロック1段分の名前
+#[derive(Default)]
+struct Scope {
+    names: HashMap<String, Name>,
+    tags: HashMap<String, Tag>,
+    /// このブロックまでに確保したローカル変数領域の大きさ
+    stack_size: usize,
+}
+
+/// 関数の戻り値と引数の型
+#[derive(Clone)]
+struct FuncType {
+    res_type: Type,
+    params: Vec<Type>,
+}
+
+/// 関数内のラベル. goto が先に出てきたときは番号だけ振っておく
+struct GotoLabel {
+    id: usize,
+    defined: bool,
+    /// 最初にこのラベルへ飛んだ goto の位置
+    used: Option<Span>,
+}
+
+/// 読んでいる switch 文の case と default の飛び先の番号
+struct Cases {
+    /// case の値をそろえる型
+    ty: Type,
+    cases: Vec<(i64, usize)>,
+    default: Option<usize>,
+}
+
+/// 構文木を作る間に持ち回る情報
+struct Env<'a> {
+    /// ソースコード全体. 読み直しに使う
+    source: &'a str,
+    /// ブロックごとに1段積む. 一番外側はファイルスコープ
+    scopes: Vec<Scope>,
+    /// 関数内で同時に確保した領域の最大値
+    max_stack: usize,
+    /// 大域変数
+    globals: HashMap<String, Type>,
+    /// 宣言された関数
+    funcs: HashMap<String, FuncType>,
+    /// 読んでいる関数の戻り値の型
+    res_type: Type,
+    /// break, continue で飛ぶ先の文の番号. 内側のものほど後ろ
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+    /// 飛び先の番号を振るためのカウンタ
+    jump_counter: usize,
+    switches: Vec<Cases>,
+    /// 読んでいる関数のラベル
+    labels: HashMap<String, GotoLabel>,
+    /// 文字列リテラルの中身. 添字がラベルの番号になる
+    strings: Vec<Vec<u8>>,
+    /// 読んでいる式のキャストの括弧を調べ終えたか
+    casts_checked: bool,
+}
+
+impl Env<'_> {
+    /// 確保済みのローカル変数領域の大きさ
+    fn stack_size(&self) -> usize {
+        self.scopes.last().unwrap().stack_size
+    }
+
+    fn enter_scope(&mut self) {
+        let stack_size = self.stack_size();
+        self.scopes.push(Scope {
+            stack_size,
+            ..Default::default()
+        });
+    }
+
+    /// ブロックを抜ける. そこで確保した領域は兄弟のブロックで使い回す
+    fn leave_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    /// 内側のスコープから順に名前を探す
+    fn find_name(&self, name: &str) -> Option<&Name> {
+        self.scopes.iter().rev().find_map(|x| x.names.get(name))
+    }
+
+    /// 型名として見えている typedef を探す. 内側で変数に隠されていれば見えない
+    fn find_typedef(&self, name: &str) -> Option<&Type> {
+        match self.find_name(name) {
+            Some(Name::Typedef(t)) => Some(t),
+            _ => None,
+        }
+    }
+
+    fn find_tag(&self, name: &str) -> Option<Tag> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|x| x.tags.get(name))
+            .cloned()
+    }
+
+    /// ラベルの番号. まだ出てきていなければ新しく振る
+    fn label(&mut self, name: &str) -> &mut GotoLabel {
+        let counter = &mut self.jump_counter;
+        self.labels.entry(name.into()).or_insert_with(|| {
+            *counter += 1;
+            GotoLabel {
+                id: *counter,
+                defined: false,
+                used: None,
+            }
+        })
+    }
+
+    /// ローカル変数領域を確保して rbp からの距離を返す
+    fn alloc(&mut self, size: usize, align: usize) -> usize {
+        let scope = self.scopes.last_mut().unwrap();
+        let offset = (scope.stack_size + size).next_multiple_of(align);
+        scope.stack_size = offset;
+        self.max_stack = self.max_stack.max(offset);
+        offset
+    }
+
+    /// 今のスコープに同じ名前がないか調べる. ファイルスコープでは大域変数とも比べる
+    fn check_redefinition(&self, name: &pest::iterators::Pair<Rule>) -> Result<(), CompileError> {
+        let file_scope = self.scopes.len() == 1;
+        if self
+            .scopes
+            .last()
+            .unwrap()
+            .names
+            .contains_key(name.as_str())
+            || (file_scope && self.globals.contains_key(name.as_str()))
+        {
+            return Err(CompileError::new(
+                name.as_span(),
+                format!("redefinition of `{}`", name.as_str()),
+            ));
+        }
+        Ok(())
+    }
+
+    /// 今のスコープにローカル変数を置く
+    fn declare_local(
+        &mut self,
+        name: pest::iterators::Pair<Rule>,
+        type_name: Type,
+    ) -> Result<(), CompileError> {
+        self.check_redefinition(&name)?;
+        check_complete(&type_name, name.as_span().into(), "variable")?;
+        let offset = self.alloc(type_name.size(), type_name.align());
+        // rbp からの距離は 32 ビットの即値で扱う
+        if self.max_stack.next_multiple_of(16) > i32::MAX as usize {
+            return Err(CompileError::new(
+                name.as_span(),
+                "stack frame is too large",
+            ));
+        }
+        let info = ValInfo::new(offset, type_name);
+        let scope = self.scopes.last_mut().unwrap();
+        scope.names.insert(name.as_str().into(), Name::Var(info));
+        Ok(())
+    }
+
+    /// 今のスコープに列挙定数を置く
+    fn declare_const(
+        &mut self,
+        name: pest::iterators::Pair<Rule>,
+        value: i32,
+    ) -> Result<(), CompileError> {
+        self.check_redefinition(&name)?;
+        let scope = self.scopes.last_mut().unwrap();
+        scope.names.insert(name.as_str().into(), Name::Const(value));
+        Ok(())
+    }
+}
+
+/// 変数やメンバの型の大きさが決まっているか調べる
+fn check_complete(type_name: &Type, span: Span, what: &str) -> Result<(), CompileError> {
+    if type_name.is_incomplete() {
+        return Err(CompileError::new(
+            span,
+            format!("{} has incomplete type `{}`", what, type_name),
+        ));
+    }
+    Ok(())
+}
+
+/// レジスタで受け渡せる大きさか調べる. 16バイトを超える構造体はメモリ渡しになるが未対応
+fn check_register_passing(type_name: &Type, span: Span, what: &str) -> Result<(), CompileError> {
+    if let Type::Struct(_) = type_name {
+        if type_name.size() > 16 {
+            return Err(CompileError::new(
+                span,
+                format!("{} structs larger than 16 bytes is not supported", what),
+            ));
+        }
+        // 浮動小数点数のメンバは xmm レジスタで渡すことになるが, 汎用レジスタにしか対応していない
+        if type_name.has_float() {
+            return Err(CompileError::new(
+                span,
+                format!(
+                    "{} structs with floating-point members is not supported",
+                    what
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
 fn get_operator(rule: Rule) -> Op {
     match rule {
         Rule::addop => Op::Add,
         Rule::subop => Op::Sub,
         Rule::mulop => Op::Mul,
         Rule::divop => Op::Div,
+        Rule::modop => Op::Mod,
+        Rule::andbit => Op::BitAnd,
+        Rule::orbit => Op::BitOr,
+        Rule::xorbit => Op::BitXor,
+        Rule::shlop => Op::Shl,
+        Rule::shrop => Op::Shr,
         Rule::eqop => Op::Eq,
         Rule::nqop => Op::Neq,
         Rule::ltop => Op::Lt,
         Rule::leop => Op::Le,
         Rule::gtop => Op::Gt,
         Rule::geop => Op::Ge,
+        Rule::andop => Op::LogAnd,
+        Rule::orop => Op::LogOr,
         Rule::asnop => Op::Assign,
         _ => {
             panic!()
@@ -32,115 +275,1024 @@ fn get_operator(rule: Rule) -> Op {
     }
 }
 
-fn build_ast_from_expr(
-    pair: pest::iterators::Pair<Rule>,
-    env: &mut HashMap<String, ValInfo>,
-) -> Result<Expr, Error<Rule>> {
-    match pair.as_rule() {
-        Rule::assign | Rule::equation | Rule::relational | Rule::addminus | Rule::factor => {
-            let mut inner = pair.into_inner();
-            let mut ret = build_ast_from_expr(inner.next().unwrap(), env)?;
-            loop {
-                if let Some(op) = inner.next() {
-                    ret = Expr::BinOp {
-                        lhs: Box::new(ret),
-                        op: get_operator(op.as_rule()),
-                        rhs: Box::new(build_ast_from_expr(inner.next().unwrap(), env)?),
-                    }
-                } else {
-                    break;
-                }
-            }
-            Ok(ret)
+/// 条件演算子の式を作る. 2つの選択肢は両方とも算術型かポインタか, 同じ構造体でなければならない
+fn new_conditional(cond: Expr, then: Expr, els: Expr, span: Span) -> Result<Expr, CompileError> {
+    let (tt, et) = (then.get_type(), els.get_type());
+    let ok = match (&tt, &et) {
+        (Type::Struct(a), Type::Struct(b)) => Rc::ptr_eq(a, b),
+        (Type::Struct(_), _) | (_, Type::Struct(_)) => false,
+        (t, p) | (p, t) if t.is_float() && p.base().is_some() => false,
+        _ => true,
+    };
+    if !ok || matches!(cond.get_type(), Type::Struct(_)) {
+        return Err(CompileError::new(
+            span,
+            format!("incompatible operand types (`{}` and `{}`)", tt, et),
+        ));
+    }
+    // 両方とも算術型なら通常の算術型変換でそろえる
+    let (then, els) = if tt.is_arith() && et.is_arith() {
+        let ty = usual_arith(&tt, &et);
+        (
+            implicit_cast(then, &ty, "converting")?,
+            implicit_cast(els, &ty, "converting")?,
+        )
+    } else {
+        (then, els)
+    };
+    Ok(Expr::new(
+        ExprKind::Cond {
+            cond: Box::new(cond),
+            then: Box::new(then),
+            els: Box::new(els),
+        },
+        span,
+    ))
+}
+
+/// `++`, `--` で足すか引くか
+fn step_operator(rule: Rule) -> Op {
+    match rule {
+        Rule::incop => Op::Add,
+        _ => Op::Sub,
+    }
+}
+
+/// 複合代入の式を作る. ポインタへの加減算は指す先の大きさでスケールする
+/// 関数と配列は代入の左辺にできない
+fn check_assignable(lhs: &Expr) -> Result<(), CompileError> {
+    match lhs.get_type() {
+        Type::Func(_, _) => Err(CompileError::new(lhs.span, "expression is not assignable")),
+        ty @ Type::Array(_, _) => Err(CompileError::new(
+            lhs.span,
+            format!("array type `{}` is not assignable", ty),
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn new_opassign(
+    lhs: Expr,
+    op: Op,
+    rhs: Expr,
+    post: bool,
+    span: Span,
+) -> Result<Expr, CompileError> {
+    check_assignable(&lhs)?;
+    let (lt, rt) = (lhs.get_type(), rhs.get_type());
+    let rhs = match (&op, &lt) {
+        _ if !rt.is_arith() => None,
+        _ if (lt.is_float() || rt.is_float()) && !is_float_op(&op) => None,
+        (Op::Add | Op::Sub, Type::Ptr(t)) if rt.is_integer() => {
+            let rhs = implicit_cast(rhs, &Type::Long, "converting")?;
+            let size = Expr::new(ExprKind::Integer(t.size() as i64, Type::Int), span);
+            Some(binop(rhs, Op::Mul, size, span))
         }
-        Rule::unary => {
-            let mut inner = pair.into_inner();
-            let content = inner.next().unwrap();
-            match content.as_rule() {
-                Rule::atom => build_ast_from_expr(content, env),
-                Rule::addr => Ok(Expr::Addr(Box::new(build_ast_from_expr(
-                    inner.next().unwrap(),
-                    env,
-                )?))),
-                Rule::deref => Ok(Expr::Dref(Box::new(build_ast_from_expr(
-                    inner.next().unwrap(),
-                    env,
-                )?))),
-                _ => Ok(Expr::BinOp {
-                    lhs: Box::new(Expr::Integer(0)),
-                    op: get_operator(content.as_rule()),
-                    rhs: Box::new(build_ast_from_expr(inner.next().unwrap(), env)?),
-                }),
+        // 右辺は演算する型にそろえておく. 左辺は値を読んだあとで変換する
+        (Op::Shl | Op::Shr, _) if lt.is_integer() => {
+            Some(implicit_cast(rhs, &rt.promote(), "converting")?)
+        }
+        _ if lt.is_arith() => Some(implicit_cast(rhs, &usual_arith(&lt, &rt), "converting")?),
+        _ => None,
+    };
+    let Some(rhs) = rhs else {
+        return Err(CompileError::new(
+            span,
+            format!(
+                "invalid operands to binary expression (`{}` and `{}`)",
+                lt, rt
+            ),
+        ));
+    };
+    Ok(Expr::new(
+        ExprKind::OpAssign {
+            lhs: Box::new(lhs),
+            op,
+            rhs: Box::new(rhs),
+            post,
+        },
+        span,
+    ))
+}
+
+/// 代入, 引数渡し, return での暗黙の型変換. 算術型へ変換するときは Cast を挟む
+fn implicit_cast(expr: Expr, ty: &Type, what: &str) -> Result<Expr, CompileError> {
+    let from = expr.get_type();
+    if from == *ty {
+        return Ok(expr);
+    }
+    let incompatible = match (&from, ty) {
+        (Type::Struct(_), _) | (_, Type::Struct(_)) => true,
+        (f, p) | (p, f) if f.is_float() && p.base().is_some() => true,
+        _ => false,
+    };
+    if incompatible {
+        return Err(CompileError::new(
+            expr.span,
+            format!("{} `{}` to incompatible type `{}`", what, from, ty),
+        ));
+    }
+    if !ty.is_arith() {
+        return Ok(expr);
+    }
+    let span = expr.span;
+    Ok(Expr::new(
+        ExprKind::Cast {
+            expr: Box::new(expr),
+            type_name: ty.clone(),
+        },
+        span,
+    ))
+}
+
+/// 明示的なキャスト. スカラ型どうしでしか変換できない
+fn new_cast(expr: Expr, type_name: Type, span: Span) -> Result<Expr, CompileError> {
+    if !type_name.is_scalar() {
+        return Err(CompileError::new(
+            span,
+            format!(
+                "used type `{}` where arithmetic or pointer type is required",
+                type_name
+            ),
+        ));
+    }
+    let from = expr.get_type();
+    // 配列と関数はポインタとして扱う
+    if !from.is_scalar() && !matches!(from, Type::Array(_, _) | Type::Func(_, _)) {
+        return Err(CompileError::new(
+            expr.span,
+            format!(
+                "operand of type `{}` where arithmetic or pointer type is required",
+                from
+            ),
+        ));
+    }
+    // ポインタと浮動小数点数は互いに変換できない
+    if type_name.is_float() && from.base().is_some() {
+        return Err(CompileError::new(
+            span,
+            format!("pointer cannot be cast to type `{}`", type_name),
+        ));
+    }
+    if from.is_float() && type_name.base().is_some() {
+        return Err(CompileError::new(
+            span,
+            format!(
+                "operand of type `{}` cannot be cast to a pointer type",
+                from
+            ),
+        ));
+    }
+    Ok(Expr::new(
+        ExprKind::Cast {
+            expr: Box::new(expr),
+            type_name,
+        },
+        span,
+    ))
+}
+
+/// 整数しかオペランドに取れない演算子
+fn is_integer_op(op: &Op) -> bool {
+    matches!(
+        op,
+        Op::Mul | Op::Div | Op::Mod | Op::BitAnd | Op::BitOr | Op::BitXor | Op::Shl | Op::Shr
+    )
+}
+
+/// 浮動小数点数もオペランドに取れる演算子
+fn is_float_op(op: &Op) -> bool {
+    !matches!(
+        op,
+        Op::Mod | Op::BitAnd | Op::BitOr | Op::BitXor | Op::Shl | Op::Shr
+    )
+}
+
+/// 整数定数式の値. `what` はエラーメッセージで式を指す言葉
+fn eval_integer(expr: Expr, what: &str) -> Result<i64, CompileError> {
+    if expr.get_type().is_float() {
+        return Err(CompileError::new(
+            expr.span,
+            format!("{} is not an integer constant expression", what),
+        ));
+    }
+    expr.eval().ok_or_else(|| {
+        CompileError::new(
+            expr.span,
+            format!("{} is not a compile-time constant", what),
+        )
+    })
+}
+
+/// 整数しか取れない単項演算子のオペランドを検査する
+fn check_integer(expr: &Expr) -> Result<(), CompileError> {
+    match expr.get_type() {
+        ty if ty.is_integer() => Ok(()),
+        ty => Err(CompileError::new(
+            expr.span,
+            format!("invalid argument type `{}` to unary expression", ty),
+        )),
+    }
+}
+
+fn binop(lhs: Expr, op: Op, rhs: Expr, span: Span) -> Expr {
+    Expr::new(
+        ExprKind::BinOp {
+            lhs: Box::new(lhs),
+            op,
+            rhs: Box::new(rhs),
+        },
+        span,
+    )
+}
+
+/// オペランドの型を検査して二項演算の式を作る
+///
+/// ポインタと整数の加減算は指す先の大きさでスケールし,
+/// ポインタ同士の差は要素数に直す.
+fn new_binop(lhs: Expr, op: Op, rhs: Expr, span: Span) -> Result<Expr, CompileError> {
+    if op == Op::Assign {
+        check_assignable(&lhs)?;
+    }
+    let (lt, rt) = (lhs.get_type(), rhs.get_type());
+    if op == Op::Assign && lt.is_arith() {
+        let rhs = implicit_cast(rhs, &lt, "assigning")?;
+        return Ok(binop(lhs, op, rhs, span));
+    }
+    // 構造体どうしは同じ型への代入だけできる
+    if let (Type::Struct(_), _) | (_, Type::Struct(_)) = (&lt, &rt) {
+        return match (&op, &lt, &rt) {
+            (Op::Assign, Type::Struct(a), Type::Struct(b)) if Rc::ptr_eq(a, b) => {
+                Ok(binop(lhs, op, rhs, span))
             }
+            (Op::Assign, _, _) => Err(CompileError::new(
+                span,
+                format!("assigning to `{}` from incompatible type `{}`", lt, rt),
+            )),
+            _ => Err(CompileError::new(
+                span,
+                format!(
+                    "invalid operands to binary expression (`{}` and `{}`)",
+                    lt, rt
+                ),
+            )),
+        };
+    }
+    // ポインタに足す整数は long にしてから要素の大きさを掛ける
+    let scale = |n: Expr, t: &Type| {
+        let size = Expr::new(ExprKind::Integer(t.size() as i64, Type::Int), span);
+        Ok::<_, CompileError>(binop(
+            implicit_cast(n, &Type::Long, "converting")?,
+            Op::Mul,
+            size,
+            span,
+        ))
+    };
+    let size = |t: &Type| Expr::new(ExprKind::Integer(t.size() as i64, Type::Int), span);
+    let invalid = || {
+        CompileError::new(
+            span,
+            format!(
+                "invalid operands to binary expression (`{}` and `{}`)",
+                lt, rt
+            ),
+        )
+    };
+    match (&op, lt.base(), rt.base()) {
+        // 浮動小数点数はポインタと演算できず, 剰余やビット演算もできない
+        (op, l, r)
+            if (lt.is_float() || rt.is_float())
+                && (l.is_some() || r.is_some() || !is_float_op(op)) =>
+        {
+            Err(invalid())
         }
-        Rule::atom => {
-            let mut inner = pair.into_inner();
-            let content = inner.next().unwrap();
-            match content.as_rule() {
-                Rule::ident => {
-                    let name = String::from(content.as_str());
-                    match env.get(&name) {
-                        Some(info) => Ok(Expr::Var {
-                            name,
-                            info: info.clone(),
-                        }),
-                        None => Err(Error::new_from_span(
-                            ErrorVariant::CustomError {
-                                message: String::from(format!("{} is undefined!", name)),
-                            },
-                            content.as_span(),
-                        )),
+        (Op::Add | Op::Sub, Some(t), None) => {
+            let rhs = scale(rhs, t)?;
+            Ok(binop(lhs, op, rhs, span))
+        }
+        (Op::Add, None, Some(t)) => {
+            let lhs = scale(lhs, t)?;
+            Ok(binop(rhs, op, lhs, span))
+        }
+        (Op::Sub, Some(t), Some(_)) => {
+            let size = size(t);
+            Ok(binop(binop(lhs, op, rhs, span), Op::Div, size, span))
+        }
+        (Op::Add, Some(_), Some(_)) | (Op::Sub, None, Some(_)) => Err(invalid()),
+        (op, l, r) if is_integer_op(op) && (l.is_some() || r.is_some()) => Err(invalid()),
+        // 通常の算術型変換. シフトは両辺を別々に整数拡張する
+        (Op::LogAnd | Op::LogOr, _, _) => Ok(binop(lhs, op, rhs, span)),
+        _ if lt.is_arith() && rt.is_arith() => {
+            let (lty, rty) = match op {
+                Op::Shl | Op::Shr => (lt.promote(), rt.promote()),
+                _ => {
+                    let ty = usual_arith(&lt, &rt);
+                    (ty.clone(), ty)
+                }
+            };
+            let lhs = implicit_cast(lhs, &lty, "converting")?;
+            let rhs = implicit_cast(rhs, &rty, "converting")?;
+            Ok(binop(lhs, op, rhs, span))
+        }
+        _ => Ok(binop(lhs, op, rhs, span)),
+    }
+}
+
+/// 文字列リテラルのエスケープシーケンスを展開する. `start` は引用符の位置
+fn unescape(literal: &str, start: usize) -> Result<Vec<u8>, CompileError> {
+    let body = &literal[1..literal.len() - 1];
+    let mut out = vec![];
+    let mut chars = body.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        let (_, e) = chars.next().unwrap();
+        let b = match e {
+            'n' => b'\n',
+            't' => b'\t',
+            'r' => b'\r',
+            'a' => 0x07,
+            'b' => 0x08,
+            'f' => 0x0c,
+            'v' => 0x0b,
+            'e' => 0x1b,
+            '0'..='7' => {
+                let mut n = e.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match chars.peek().and_then(|&(_, c)| c.to_digit(8)) {
+                        Some(d) => {
+                            n = n * 8 + d;
+                            chars.next();
+                        }
+                        None => break,
                     }
                 }
-                Rule::num => Ok(Expr::Integer(content.as_str().parse::<i32>().unwrap())),
-                Rule::assign => build_ast_from_expr(content, env),
-                Rule::funccall => {
-                    let mut inner = content.into_inner();
-                    let name = inner.next().unwrap();
-                    assert_eq!(name.as_rule(), Rule::ident);
-                    let args = if let Some(arg) = inner.next() {
-                        arg.into_inner()
-                            .map(|x| build_ast_from_expr(x, env))
-                            .collect::<Result<_, _>>()?
-                    } else {
-                        vec![]
-                    };
-                    Ok(Expr::FunCall {
-                        name: name.as_str().into(),
-                        args,
-                    })
+                n as u8
+            }
+            'x' => {
+                let mut n: u32 = 0;
+                let mut digits = 0;
+                while let Some(d) = chars.peek().and_then(|&(_, c)| c.to_digit(16)) {
+                    n = n.saturating_mul(16).saturating_add(d);
+                    digits += 1;
+                    chars.next();
+                }
+                if digits == 0 {
+                    let pos = start + 1 + i;
+                    return Err(CompileError::new(
+                        Span::new(pos, pos + 2),
+                        "\\x used with no following hex digits",
+                    ));
                 }
-                _ => {
-                    return Err(Error::new_from_span(
-                        ErrorVariant::CustomError {
-                            message: String::from("innerError when parsing atom"),
-                        },
-                        content.as_span(),
-                    ))
+                if n > 0xff {
+                    let pos = start + 1 + i;
+                    return Err(CompileError::new(
+                        Span::new(pos, pos + 2 + digits),
+                        "hex escape sequence out of range",
+                    ));
                 }
+                n as u8
             }
+            c => c as u8,
+        };
+        out.push(b);
+    }
+    Ok(out)
+}
+
+/// 整数リテラルを読んで値と型を返す
+///
+/// 型は接尾辞と基数で決まる候補のうち, 値が入る最初のものにする.
+fn parse_integer(literal: &str, span: Span) -> Result<(i64, Type), CompileError> {
+    let lower = literal.to_ascii_lowercase();
+    let (radix, body) = if lower.starts_with("0x") {
+        (16, &literal[2..])
+    } else if lower.starts_with("0b") {
+        (2, &literal[2..])
+    } else if literal.starts_with('0') {
+        (8, literal)
+    } else {
+        (10, literal)
+    };
+    let len = body
+        .find(|c: char| !(c.is_ascii_digit() || radix == 16 && c.is_ascii_hexdigit()))
+        .unwrap_or(body.len());
+    let (digits, suffix) = body.split_at(len);
+    if let Some(c) = digits.chars().find(|c| c.to_digit(radix).is_none()) {
+        let kind = if radix == 8 { "octal" } else { "binary" };
+        return Err(CompileError::new(
+            span,
+            format!("invalid digit `{}` in {} constant", c, kind),
+        ));
+    }
+    let invalid_suffix = || {
+        let suffix = if digits.is_empty() {
+            &literal[1..]
+        } else {
+            suffix
+        };
+        CompileError::new(
+            span,
+            format!("invalid suffix `{}` on integer constant", suffix),
+        )
+    };
+    if digits.is_empty() || suffix.contains("lL") || suffix.contains("Ll") {
+        return Err(invalid_suffix());
+    }
+    let (unsigned, long) = match suffix.to_ascii_lowercase().as_str() {
+        "" => (false, false),
+        "u" => (true, false),
+        "l" | "ll" => (false, true),
+        "ul" | "lu" | "ull" | "llu" => (true, true),
+        _ => return Err(invalid_suffix()),
+    };
+    let value = u64::from_str_radix(digits, radix).map_err(|_| {
+        CompileError::new(
+            span,
+            "integer literal is too large to be represented in any integer type",
+        )
+    })? as i64;
+    // 10進数は接尾辞に u がなければ符号付きの型だけが候補になる
+    let candidates = match (unsigned, long, radix == 10) {
+        (false, false, true) => vec![Type::Int, Type::Long],
+        (false, false, false) => vec![Type::Int, Type::UInt, Type::Long, Type::ULong],
+        (true, false, _) => vec![Type::UInt, Type::ULong],
+        (false, true, true) => vec![Type::Long],
+        (false, true, false) => vec![Type::Long, Type::ULong],
+        (true, true, _) => vec![Type::ULong],
+    };
+    // long にも入らない10進数は unsigned long として扱う
+    let ty = candidates
+        .into_iter()
+        .find(|t| *t == Type::ULong || value >= 0 && t.wrap(value) == value)
+        .unwrap_or(Type::ULong);
+    Ok((value, ty))
+}
+
+/// ソースの `span` の部分を `rule` で読み直して `build` に渡す
+///
+/// 手前を空白で埋めて位置を保つので, 読み直した構文木の Span もソース全体に対するものになる.
+fn reparse<T>(
+    rule: Rule,
+    span: Span,
+    env: &mut Env,
+    build: fn(pest::iterators::Pair<Rule>, &mut Env) -> Result<T, CompileError>,
+) -> Result<T, CompileError> {
+    let padded = " ".repeat(span.start) + &env.source[span.start..span.end];
+    let pair = CalcParser::parse(rule, &padded)
+        .map_err(CompileError::from)?
+        .next()
+        .unwrap();
+    build(pair, env)
+}
+
+/// `typename` の先頭が今のスコープで型名として見えているか
+fn is_typename(pair: &pest::iterators::Pair<Rule>, env: &Env) -> bool {
+    let base = pair.clone().into_inner().next().unwrap();
+    match base.as_rule() {
+        Rule::structspec | Rule::enumspec | Rule::arithspec => true,
+        _ => env.find_typedef(base.as_str()).is_some(),
+    }
+}
+
+/// 括弧の中の `typename` が型を表しているか. `*` が付いていれば式ではありえない
+fn names_type(typename: &pest::iterators::Pair<Rule>, env: &Env) -> bool {
+    is_typename(typename, env) || typename.clone().into_inner().count() > 1
+}
+
+/// 型名でない名前を囲んだ `cast` の括弧を空白にした式の文字列. そのような `cast` がなければ None
+fn unmask_casts(pair: &pest::iterators::Pair<Rule>, env: &Env) -> Option<String> {
+    let start = pair.as_span().start();
+    let mut text = (" ".repeat(start) + pair.as_str()).into_bytes();
+    let mut found = false;
+    for x in pair.clone().into_inner().flatten() {
+        if x.as_rule() == Rule::cast && !names_type(&x.clone().into_inner().next().unwrap(), env) {
+            text[x.as_span().start()] = b' ';
+            text[x.as_span().end() - 1] = b' ';
+            found = true;
         }
+    }
+    found.then(|| String::from_utf8(text).unwrap())
+}
+
+/// `sizeof` の値. 式は型を調べるだけで評価しない
+fn build_ast_from_sizeof(
+    pair: pest::iterators::Pair<Rule>,
+    span: Span,
+    env: &mut Env,
+) -> Result<Expr, CompileError> {
+    let type_name = if pair.as_rule() == Rule::sizeoftype {
+        let mut inner = pair.clone().into_inner();
+        let typename = inner.next().unwrap();
+        if names_type(&typename, env) {
+            let type_name = build_ast_from_typename(typename, env)?;
+            build_array_type(type_name, &mut inner, env)?
+        } else {
+            // sizeof(x) の x が型名でなければ括弧つきの式
+            reparse(
+                Rule::reunary,
+                pair.as_span().into(),
+                env,
+                build_ast_from_expr,
+            )?
+            .get_type()
+        }
+    } else {
+        build_ast_from_expr(pair, env)?.get_type()
+    };
+    Ok(Expr::new(
+        ExprKind::Integer(type_name.size() as i64, Type::Int),
+        span,
+    ))
+}
+
+/// 名前だけの式. 変数, 列挙定数, 大域変数, 関数の順に探す
+fn build_ast_from_ident(
+    pair: pest::iterators::Pair<Rule>,
+    env: &mut Env,
+) -> Result<Expr, CompileError> {
+    let span = Span::from(pair.as_span());
+    let name = String::from(pair.as_str());
+    match env.find_name(&name) {
+        Some(Name::Var(info)) => {
+            return Ok(Expr::new(
+                ExprKind::Var {
+                    name,
+                    info: info.clone(),
+                },
+                span,
+            ))
+        }
+        Some(Name::Const(n)) => {
+            return Ok(Expr::new(ExprKind::Integer(*n as i64, Type::Int), span))
+        }
+        Some(Name::Typedef(_)) => {
+            return Err(CompileError::new(
+                span,
+                format!("unexpected type name `{}`: expected expression", name),
+            ))
+        }
+        None => {}
+    }
+    let type_name = match (env.globals.get(&name), env.funcs.get(&name)) {
+        (Some(type_name), _) => type_name.clone(),
+        (None, Some(f)) => Type::Func(Box::new(f.res_type.clone()), f.params.clone()),
+        (None, None) => return Err(CompileError::new(span, format!("{} is undefined!", name))),
+    };
+    Ok(Expr::new(ExprKind::Global { name, type_name }, span))
+}
+
+/// 関数呼び出しの実引数を読む
+fn build_args(
+    pair: Option<pest::iterators::Pair<Rule>>,
+    env: &mut Env,
+) -> Result<Vec<Expr>, CompileError> {
+    let Some(pair) = pair else {
+        return Ok(vec![]);
+    };
+    let args = pair
+        .into_inner()
+        .map(|x| build_ast_from_expr(x, env))
+        .collect::<Result<Vec<_>, _>>()?;
+    for arg in &args {
+        check_register_passing(&arg.get_type(), arg.span, "passing")?;
+    }
+    Ok(args)
+}
+
+/// 関数か関数ポインタ `func` の呼び出し. 引数は宣言された型に変換する
+fn new_funcall(
+    func: Expr,
+    args: Vec<Expr>,
+    span: Span,
+    env: &mut Env,
+) -> Result<Expr, CompileError> {
+    let ty = func.get_type();
+    let Type::Func(res_type, params) = ty.base().unwrap_or(&ty) else {
+        return Err(CompileError::new(
+            func.span,
+            format!(
+                "called object type `{}` is not a function or function pointer",
+                ty
+            ),
+        ));
+    };
+    if params.len() != args.len() {
+        return Err(CompileError::new(
+            span,
+            format!(
+                "`{}` takes {} arguments but {} were given",
+                &env.source[func.span.start..func.span.end],
+                params.len(),
+                args.len()
+            ),
+        ));
+    }
+    let args = args
+        .into_iter()
+        .zip(params)
+        .map(|(arg, ty)| implicit_cast(arg, ty, "passing"))
+        .collect::<Result<_, _>>()?;
+    let res_type = (**res_type).clone();
+    check_register_passing(&res_type, span, "returning")?;
+    // 構造体の戻り値を置いておく領域
+    let ret_buf = match res_type {
+        Type::Struct(_) => Some(env.alloc(res_type.size().next_multiple_of(8), 8)),
+        _ => None,
+    };
+    Ok(Expr::new(
+        ExprKind::FunCall {
+            func: Box::new(func),
+            args,
+            res_type,
+            ret_buf,
+        },
+        span,
+    ))
+}
+
+/// 括弧を空白にした式を代入式として読み直す
+fn parse_reassign(text: &str) -> Result<pest::iterators::Pair<'_, Rule>, CompileError> {
+    Ok(CalcParser::parse(Rule::reassign, text)
+        .map_err(CompileError::from)?
+        .next()
+        .unwrap())
+}
+
+/// 式の構文の段
+fn is_expr_rule(rule: Rule) -> bool {
+    matches!(
+        rule,
+        Rule::commaexpr
+            | Rule::assign
+            | Rule::conditional
+            | Rule::logor
+            | Rule::logand
+            | Rule::bitor
+            | Rule::bitxor
+            | Rule::bitand
+            | Rule::equation
+            | Rule::relational
+            | Rule::shift
+            | Rule::addminus
+            | Rule::factor
+            | Rule::unary
+            | Rule::postfix
+            | Rule::atom
+    )
+}
+
+fn build_ast_from_expr(
+    pair: pest::iterators::Pair<Rule>,
+    env: &mut Env,
+) -> Result<Expr, CompileError> {
+    // 式は深く入れ子になるので, 演算子のない段は再帰せずに読み飛ばす
+    let mut pair = pair;
+    while is_expr_rule(pair.as_rule()) && (pair.as_rule() != Rule::assign || env.casts_checked) {
+        let mut inner = pair.clone().into_inner();
+        match (inner.next(), inner.next()) {
+            (Some(child), None) if is_expr_rule(child.as_rule()) => pair = child,
+            _ => break,
+        }
+    }
+    match pair.as_rule() {
+        Rule::assign if !env.casts_checked => build_ast_from_assign(pair, env),
+        Rule::commaexpr => build_ast_from_comma(pair, env),
+        Rule::conditional => build_ast_from_conditional(pair, env),
+        Rule::assign
+        | Rule::logor
+        | Rule::logand
+        | Rule::bitor
+        | Rule::bitxor
+        | Rule::bitand
+        | Rule::equation
+        | Rule::relational
+        | Rule::shift
+        | Rule::addminus
+        | Rule::factor => build_ast_from_binary(pair, env),
+        Rule::unary => build_ast_from_unary(pair, env),
+        Rule::postfix => build_ast_from_postfix(pair, env),
+        Rule::atom => build_ast_from_atom(pair, env),
         _ => {
             println!("{:?}", pair.as_str());
-            return Err(Error::new_from_span(
-                ErrorVariant::CustomError {
-                    message: String::from("innerError in parsing expr"),
-                },
+            Err(CompileError::new(
                 pair.as_span(),
-            ));
+                "innerError in parsing expr",
+            ))
         }
     }
 }
 
+/// 一番外側の代入式. (x) の x が型名でなければキャストではなく括弧で囲んだ式なので,
+/// 括弧を空白にして読み直す. 式の中で型名の見え方は変わらないので内側の式では調べない
+fn build_ast_from_assign(
+    pair: pest::iterators::Pair<Rule>,
+    env: &mut Env,
+) -> Result<Expr, CompileError> {
+    // 括弧を外すと別の括弧がキャストとして読まれることもあるので, なくなるまで繰り返す
+    let mut unmasked: Option<String> = None;
+    loop {
+        let text = match &unmasked {
+            Some(text) => unmask_casts(&parse_reassign(text)?, env),
+            None => unmask_casts(&pair, env),
+        };
+        match text {
+            Some(text) => unmasked = Some(text),
+            None => break,
+        }
+    }
+    let pair = match &unmasked {
+        Some(text) => parse_reassign(text)?,
+        None => pair,
+    };
+    env.casts_checked = true;
+    let ret = build_ast_from_binary(pair, env);
+    env.casts_checked = false;
+    ret
+}
+
+fn build_ast_from_comma(
+    pair: pest::iterators::Pair<Rule>,
+    env: &mut Env,
+) -> Result<Expr, CompileError> {
+    let mut inner = pair.into_inner();
+    let mut ret = build_ast_from_expr(inner.next().unwrap(), env)?;
+    for x in inner {
+        let rhs = build_ast_from_expr(x, env)?;
+        let span = Span::new(ret.span.start, rhs.span.end);
+        ret = Expr::new(
+            ExprKind::Comma {
+                lhs: Box::new(ret),
+                rhs: Box::new(rhs),
+            },
+            span,
+        );
+    }
+    Ok(ret)
+}
+
+fn build_ast_from_conditional(
+    pair: pest::iterators::Pair<Rule>,
+    env: &mut Env,
+) -> Result<Expr, CompileError> {
+    let span = Span::from(pair.as_span());
+    let mut inner = pair.into_inner();
+    let cond = build_ast_from_expr(inner.next().unwrap(), env)?;
+    let Some(then) = inner.next() else {
+        return Ok(cond);
+    };
+    let then = build_ast_from_expr(then, env)?;
+    let els = build_ast_from_expr(inner.next().unwrap(), env)?;
+    new_conditional(cond, then, els, span)
+}
+
+/// 二項演算子や代入演算子でつながった式
+fn build_ast_from_binary(
+    pair: pest::iterators::Pair<Rule>,
+    env: &mut Env,
+) -> Result<Expr, CompileError> {
+    let mut inner = pair.into_inner();
+    let mut ret = build_ast_from_expr(inner.next().unwrap(), env)?;
+    while let Some(op) = inner.next() {
+        let rhs = build_ast_from_expr(inner.next().unwrap(), env)?;
+        let span = op.as_span().into();
+        ret = match op.as_rule() {
+            Rule::compoundop => {
+                let op = get_operator(op.into_inner().next().unwrap().as_rule());
+                new_opassign(ret, op, rhs, false, span)?
+            }
+            rule => new_binop(ret, get_operator(rule), rhs, span)?,
+        };
+    }
+    Ok(ret)
+}
+
+fn build_ast_from_unary(
+    pair: pest::iterators::Pair<Rule>,
+    env: &mut Env,
+) -> Result<Expr, CompileError> {
+    let span = Span::from(pair.as_span());
+    let mut inner = pair.into_inner();
+    let content = inner.next().unwrap();
+    match content.as_rule() {
+        Rule::postfix => build_ast_from_expr(content, env),
+        Rule::sizeof => build_ast_from_sizeof(inner.next().unwrap(), span, env),
+        Rule::cast => {
+            let type_name = build_ast_from_typename(content.into_inner().next().unwrap(), env)?;
+            let expr = build_ast_from_expr(inner.next().unwrap(), env)?;
+            new_cast(expr, type_name, span)
+        }
+        Rule::addr => Ok(Expr::new(
+            ExprKind::Addr(Box::new(build_ast_from_expr(inner.next().unwrap(), env)?)),
+            span,
+        )),
+        Rule::deref => {
+            let operand = build_ast_from_expr(inner.next().unwrap(), env)?;
+            // 関数はすぐに関数ポインタに戻るので *f は f と同じ
+            let ty = operand.get_type();
+            if let Type::Func(_, _) = ty {
+                return Ok(operand);
+            }
+            if ty.base().is_none() {
+                return Err(CompileError::new(
+                    operand.span,
+                    format!("indirection requires pointer operand (`{}` invalid)", ty),
+                ));
+            }
+            Ok(Expr::new(ExprKind::Dref(Box::new(operand)), span))
+        }
+        Rule::bitnot => {
+            let operand = build_ast_from_expr(inner.next().unwrap(), env)?;
+            check_integer(&operand)?;
+            Ok(Expr::new(ExprKind::BitNot(Box::new(operand)), span))
+        }
+        // ++x は x += 1 と同じ
+        Rule::incop | Rule::decop => new_opassign(
+            build_ast_from_expr(inner.next().unwrap(), env)?,
+            step_operator(content.as_rule()),
+            Expr::new(ExprKind::Integer(1, Type::Int), content.as_span()),
+            false,
+            span,
+        ),
+        // !x は x == 0 と同じ
+        Rule::notop => new_binop(
+            build_ast_from_expr(inner.next().unwrap(), env)?,
+            Op::Eq,
+            Expr::new(ExprKind::Integer(0, Type::Int), content.as_span()),
+            content.as_span().into(),
+        ),
+        _ => new_binop(
+            Expr::new(ExprKind::Integer(0, Type::Int), content.as_span()),
+            get_operator(content.as_rule()),
+            build_ast_from_expr(inner.next().unwrap(), env)?,
+            content.as_span().into(),
+        ),
+    }
+}
+
+/// 添字, メンバ, 後置の増減, 関数呼び出しの並び
+fn build_ast_from_postfix(
+    pair: pest::iterators::Pair<Rule>,
+    env: &mut Env,
+) -> Result<Expr, CompileError> {
+    let span = Span::from(pair.as_span());
+    let mut inner = pair.into_inner();
+    let mut ret = build_ast_from_expr(inner.next().unwrap(), env)?;
+    for suffix in inner {
+        let span = Span::new(span.start, suffix.as_span().end());
+        let rule = suffix.as_rule();
+        if let Rule::incop | Rule::decop = rule {
+            let one = Expr::new(ExprKind::Integer(1, Type::Int), suffix.as_span());
+            ret = new_opassign(ret, step_operator(rule), one, true, span)?;
+            continue;
+        }
+        if rule == Rule::callargs {
+            let args = build_args(suffix.into_inner().next(), env)?;
+            ret = new_funcall(ret, args, span, env)?;
+            continue;
+        }
+        let operand = suffix.into_inner().next().unwrap();
+        ret = match rule {
+            Rule::index => {
+                let index = build_ast_from_expr(operand, env)?;
+                // a[i] は *(a + i) と同じ
+                let addr = new_binop(ret, Op::Add, index, span)?;
+                if addr.get_type().base().is_none() {
+                    return Err(CompileError::new(
+                        span,
+                        "subscripted value is not an array or pointer",
+                    ));
+                }
+                Expr::new(ExprKind::Dref(Box::new(addr)), span)
+            }
+            Rule::arrow => {
+                // a->b は (*a).b と同じ
+                let ty = ret.get_type();
+                if !matches!(ty.base(), Some(Type::Struct(_))) {
+                    return Err(CompileError::new(
+                        ret.span,
+                        format!(
+                            "member reference type `{}` is not a pointer to a structure",
+                            ty
+                        ),
+                    ));
+                }
+                let deref = Expr::new(ExprKind::Dref(Box::new(ret)), span);
+                build_member(deref, operand, span)?
+            }
+            _ => build_member(ret, operand, span)?,
+        };
+    }
+    Ok(ret)
+}
+
+fn build_ast_from_atom(
+    pair: pest::iterators::Pair<Rule>,
+    env: &mut Env,
+) -> Result<Expr, CompileError> {
+    let span = Span::from(pair.as_span());
+    let mut inner = pair.into_inner();
+    let content = inner.next().unwrap();
+    match content.as_rule() {
+        Rule::ident => build_ast_from_ident(content, env),
+        Rule::fnum => {
+            let s = content.as_str();
+            let (digits, ty) = match s.as_bytes()[s.len() - 1] {
+                b'f' | b'F' => (&s[..s.len() - 1], Type::Float),
+                b'l' | b'L' => (&s[..s.len() - 1], Type::Double),
+                _ => (s, Type::Double),
+            };
+            Ok(Expr::new(
+                ExprKind::Float(digits.parse().unwrap(), ty),
+                span,
+            ))
+        }
+        Rule::num => {
+            let (value, ty) = parse_integer(content.as_str(), span)?;
+            Ok(Expr::new(ExprKind::Integer(value, ty), span))
+        }
+        Rule::charlit => {
+            // 文字定数は int. char と同じく符号付きで広げる
+            match unescape(content.as_str(), content.as_span().start())?[..] {
+                [c] => Ok(Expr::new(
+                    ExprKind::Integer(c as i8 as i64, Type::Int),
+                    span,
+                )),
+                [] => Err(CompileError::new(span, "empty character constant")),
+                _ => Err(CompileError::new(
+                    span,
+                    "multi-character character constant is not supported",
+                )),
+            }
+        }
+        Rule::string => {
+            let mut data = unescape(content.as_str(), content.as_span().start())?;
+            data.push(0);
+            let size = data.len();
+            env.strings.push(data);
+            Ok(Expr::new(
+                ExprKind::Str {
+                    label: env.strings.len() - 1,
+                    size,
+                },
+                span,
+            ))
+        }
+        Rule::commaexpr => build_ast_from_expr(content, env),
+        Rule::funccall => {
+            let mut inner = content.into_inner();
+            let name = inner.next().unwrap();
+            assert_eq!(name.as_rule(), Rule::ident);
+            let args = build_args(inner.next(), env)?;
+            let declared = env.find_name(name.as_str()).is_some()
+                || env.globals.contains_key(name.as_str())
+                || env.funcs.contains_key(name.as_str());
+            if declared {
+                let func = build_ast_from_ident(name, env)?;
+                return new_funcall(func, args, span, env);
+            }
+            // 宣言のない関数は int を返すものとし, float は double にして渡す
+            let args = args
+                .into_iter()
+                .map(|arg| match arg.get_type() {
+                    Type::Float => implicit_cast(arg, &Type::Double, "passing"),
+                    _ => Ok(arg),
+                })
+                .collect::<Result<_, _>>()?;
+            let func = Expr::new(
+                ExprKind::Global {
+                    name: name.as_str().into(),
+                    type_name: Type::Func(Box::new(Type::Int), vec![]),
+                },
+                name.as_span(),
+            );
+            Ok(Expr::new(
+                ExprKind::FunCall {
+                    func: Box::new(func),
+                    args,
+                    res_type: Type::Int,
+                    ret_buf: None,
+                },
+                span,
+            ))
+        }
+        _ => Err(CompileError::new(span, "innerError when parsing atom")),
+    }
+}
+
 fn build_ast_from_stmt(
     pair: pest::iterators::Pair<Rule>,
-    env: &mut HashMap<String, ValInfo>,
-) -> Result<Stmt, Error<Rule>> {
+    env: &mut Env,
+) -> Result<Stmt, CompileError> {
     match pair.as_rule() {
         Rule::res => {
             let mut inner = pair.into_inner();
             let content = inner.next().unwrap();
             let expr = build_ast_from_expr(content, env)?;
+            let expr = implicit_cast(expr, &env.res_type, "returning")?;
             Ok(Stmt::Return { expr })
         }
         Rule::ifstmt => {
@@ -160,8 +1312,12 @@ fn build_ast_from_stmt(
         Rule::whilestmt => {
             let mut inner = pair.into_inner();
             let cond = build_ast_from_expr(inner.next().unwrap(), env)?;
-            let content = Box::new(build_ast_from_stmt(inner.next().unwrap(), env)?);
-            Ok(Stmt::While { cond, content })
+            let (content, label) = build_loop_body(inner.next().unwrap(), env)?;
+            Ok(Stmt::While {
+                cond,
+                content,
+                label,
+            })
         }
         Rule::forstmt => {
             let mut inner = pair.into_inner();
@@ -191,108 +1347,727 @@ fn build_ast_from_stmt(
             } else {
                 None
             };
-            let tmp = inner.next().unwrap();
-            let content = Box::new(build_ast_from_stmt(tmp, env)?);
+            let (content, label) = build_loop_body(inner.next().unwrap(), env)?;
             Ok(Stmt::For {
                 init,
                 cond,
                 tail,
                 content,
+                label,
             })
         }
-        Rule::block => Ok(Stmt::Block(
-            pair.into_inner()
-                .into_iter()
+        Rule::dostmt => {
+            let mut inner = pair.into_inner();
+            let (content, label) = build_loop_body(inner.next().unwrap(), env)?;
+            let cond = build_ast_from_expr(inner.next().unwrap(), env)?;
+            Ok(Stmt::DoWhile {
+                content,
+                cond,
+                label,
+            })
+        }
+        Rule::switchstmt => {
+            let mut inner = pair.into_inner();
+            let cond = build_ast_from_expr(inner.next().unwrap(), env)?;
+            if !cond.get_type().is_integer() {
+                return Err(CompileError::new(
+                    cond.span,
+                    format!(
+                        "statement requires expression of integer type (`{}` invalid)",
+                        cond.get_type()
+                    ),
+                ));
+            }
+            // 比べるのは等しいかどうかだけなので, unsigned int も int として比べてよい
+            let ty = match cond.get_type().promote() {
+                t if t.size() == 4 => Type::Int,
+                _ => Type::Long,
+            };
+            let cond = implicit_cast(cond, &ty, "converting")?;
+            env.jump_counter += 1;
+            let label = env.jump_counter;
+            // switch の中の break は switch を抜ける. continue は外のループのまま
+            env.breaks.push(label);
+            env.switches.push(Cases {
+                ty,
+                cases: Vec::new(),
+                default: None,
+            });
+            let content = build_ast_from_stmt(inner.next().unwrap(), env);
+            env.breaks.pop();
+            let Cases { cases, default, .. } = env.switches.pop().unwrap();
+            Ok(Stmt::Switch {
+                cond,
+                content: Box::new(content?),
+                cases,
+                default,
+                label,
+            })
+        }
+        Rule::casestmt => {
+            let span = pair.as_span();
+            let mut inner = pair.into_inner();
+            if env.switches.is_empty() {
+                return Err(CompileError::new(
+                    Span::new(span.start(), span.start() + "case".len()),
+                    "`case` statement not in switch statement",
+                ));
+            }
+            let expr = inner.next().unwrap();
+            let expr_span = expr.as_span();
+            let value = eval_integer(build_ast_from_expr(expr, env)?, "case value")?;
+            let value = env.switches.last().unwrap().ty.wrap(value);
+            if i32::try_from(value).is_err() {
+                return Err(CompileError::new(
+                    expr_span,
+                    "case value is not representable in `int`",
+                ));
+            }
+            let switch = env.switches.last().unwrap();
+            if switch.cases.iter().any(|&(v, _)| v == value) {
+                return Err(CompileError::new(
+                    expr_span,
+                    format!("duplicate case value `{}`", value),
+                ));
+            }
+            env.jump_counter += 1;
+            let id = env.jump_counter;
+            env.switches.last_mut().unwrap().cases.push((value, id));
+            let content = build_ast_from_stmt(inner.next().unwrap(), env)?;
+            Ok(Stmt::Case(id, Box::new(content)))
+        }
+        Rule::defaultstmt => {
+            let span = Span::new(
+                pair.as_span().start(),
+                pair.as_span().start() + "default".len(),
+            );
+
+            let Some(switch) = env.switches.last() else {
+                return Err(CompileError::new(
+                    span,
+                    "`default` statement not in switch statement",
+                ));
+            };
+            if switch.default.is_some() {
+                return Err(CompileError::new(
+                    span,
+                    "multiple default labels in one switch",
+                ));
+            }
+            env.jump_counter += 1;
+            let id = env.jump_counter;
+            env.switches.last_mut().unwrap().default = Some(id);
+            let content = build_ast_from_stmt(pair.into_inner().next().unwrap(), env)?;
+            Ok(Stmt::Case(id, Box::new(content)))
+        }
+        Rule::labeled => {
+            let mut inner = pair.into_inner();
+            let name = inner.next().unwrap();
+            let label = env.label(name.as_str());
+            if label.defined {
+                return Err(CompileError::new(
+                    name.as_span(),
+                    format!("redefinition of label `{}`", name.as_str()),
+                ));
+            }
+            label.defined = true;
+            let id = label.id;
+            let content = build_ast_from_stmt(inner.next().unwrap(), env)?;
+            Ok(Stmt::Labeled(id, Box::new(content)))
+        }
+        Rule::gotostmt => {
+            let name = pair.into_inner().next().unwrap();
+            let label = env.label(name.as_str());
+            label.used.get_or_insert(name.as_span().into());
+            Ok(Stmt::Goto(label.id))
+        }
+        Rule::breakstmt => match env.breaks.last() {
+            Some(label) => Ok(Stmt::Break(*label)),
+            None => Err(CompileError::new(
+                pair.as_span(),
+                "`break` statement not in loop or switch statement",
+            )),
+        },
+
+        Rule::continuestmt => match env.continues.last() {
+            Some(label) => Ok(Stmt::Continue(*label)),
+            None => Err(CompileError::new(
+                pair.as_span(),
+                "`continue` statement not in loop",
+            )),
+        },
+        Rule::block => {
+            env.enter_scope();
+            let body = pair
+                .into_inner()
                 .map(|x| build_ast_from_stmt(x, env))
-                .collect::<Result<_, _>>()?,
-        )),
+                .collect::<Result<_, _>>();
+            env.leave_scope();
+            Ok(Stmt::Block(body?))
+        }
         Rule::expr => {
             let mut inner = pair.into_inner();
             let content = inner.next().unwrap();
             let expr = build_ast_from_expr(content, env)?;
             Ok(Stmt::Calc { content: expr })
         }
-        Rule::declare => {
+        Rule::typedef => {
             let mut inner = pair.into_inner();
-            let type_name = inner.next().unwrap();
-            let var_name = inner.next().unwrap().as_str();
-            let offset = (env.len() + 1) * 8;
-            let info = ValInfo::new(offset, build_ast_from_typename(type_name)?);
-            env.insert(String::from(var_name), info);
+            inner.next();
+            let type_name = build_ast_from_typename(inner.next().unwrap(), env)?;
+            let (name, type_name) = build_declarator(type_name, &mut inner, env)?;
+            env.check_redefinition(&name)?;
+            let scope = env.scopes.last_mut().unwrap();
+            scope
+                .names
+                .insert(name.as_str().into(), Name::Typedef(type_name));
             Ok(Stmt::Declare)
         }
-        _ => {
-            return Err(Error::new_from_span(
-                ErrorVariant::CustomError {
-                    message: String::from("innerError in parsing stmt"),
-                },
-                pair.as_span(),
+        Rule::declare => {
+            let mut inner = pair.clone().into_inner();
+            let typename = inner.peek().unwrap();
+            // 先頭が型名でなければ `a * b;`, `a;`, `f(*p)(x);` という式文. `a b;` は式になりえない
+            let has_pointer = typename.clone().into_inner().count() > 1;
+            let declarator = inner.clone().nth(1);
+            let is_expr = match declarator {
+                Some(x) => has_pointer || x.as_rule() == Rule::funcptr,
+                None => true,
+            };
+            if !is_typename(&typename, env) && is_expr {
+                return reparse(
+                    Rule::restmt,
+                    pair.as_span().into(),
+                    env,
+                    build_ast_from_stmt,
+                );
+            }
+            let type_name = build_ast_from_typename(inner.next().unwrap(), env)?;
+            // `struct S { ... };` のように変数を伴わないこともある
+            if inner.peek().is_some() {
+                let (var_name, type_name) = build_declarator(type_name, &mut inner, env)?;
+                env.declare_local(var_name, type_name)?;
+            }
+            Ok(Stmt::Declare)
+        }
+        _ => Err(CompileError::new(
+            pair.as_span(),
+            "innerError in parsing stmt",
+        )),
+    }
+}
+
+/// 構造体の値 `expr` からメンバを取り出す式を作る
+fn build_member(
+    expr: Expr,
+    name: pest::iterators::Pair<Rule>,
+    span: Span,
+) -> Result<Expr, CompileError> {
+    let ty = expr.get_type();
+    let Type::Struct(s) = &ty else {
+        return Err(CompileError::new(
+            expr.span,
+            format!("member reference base type `{}` is not a structure", ty),
+        ));
+    };
+    if !s.is_complete() {
+        return Err(CompileError::new(
+            expr.span,
+            format!("incomplete definition of type `{}`", ty),
+        ));
+    }
+    let Some(member) = s.member(name.as_str()) else {
+        return Err(CompileError::new(
+            name.as_span(),
+            format!("no member named `{}` in `{}`", name.as_str(), ty),
+        ));
+    };
+    Ok(Expr::new(
+        ExprKind::Member {
+            expr: Box::new(expr),
+            member: member.clone(),
+        },
+        span,
+    ))
+}
+
+/// ループの本体を読む. その中の break, continue はこのループへ飛ぶ
+fn build_loop_body(
+    pair: pest::iterators::Pair<Rule>,
+    env: &mut Env,
+) -> Result<(Box<Stmt>, usize), CompileError> {
+    env.jump_counter += 1;
+    let label = env.jump_counter;
+    env.breaks.push(label);
+    env.continues.push(label);
+    let content = build_ast_from_stmt(pair, env);
+    env.breaks.pop();
+    env.continues.pop();
+    Ok((Box::new(content?), label))
+}
+
+/// 変数名に続く `[n]` を読んで配列の型を作る. 長さは整数の定数式
+fn build_array_type(
+    mut type_name: Type,
+    inner: &mut pest::iterators::Pairs<Rule>,
+    env: &mut Env,
+) -> Result<Type, CompileError> {
+    let mut lens = vec![];
+    while let Some(x) = inner.peek().filter(|x| x.as_rule() == Rule::arraylen) {
+        inner.next();
+        let n = x.into_inner().next().unwrap();
+        let span = n.as_span();
+        let expr = build_ast_from_expr(n, env)?;
+        let ty = expr.get_type();
+        let value = if ty.is_integer() {
+            eval_integer(expr, "array size").ok()
+        } else {
+            None
+        };
+        let Some(value) = value else {
+            return Err(CompileError::new(
+                span,
+                "array size is not an integer constant",
             ));
+        };
+        let len = if ty.is_unsigned() {
+            value as u64 as usize
+        } else {
+            usize::try_from(value)
+                .map_err(|_| CompileError::new(span, "array has negative size"))?
+        };
+        lens.push((len, span));
+    }
+    // int a[2][3] は「int[3] の長さ2の配列」なので後ろから包む
+    for (n, span) in lens.into_iter().rev() {
+        // 大きさやメンバの位置は 32 ビットの即値で扱うので i32 に収まらなければならない
+        if type_name
+            .size()
+            .checked_mul(n)
+            .is_none_or(|x| x > i32::MAX as usize)
+        {
+            return Err(CompileError::new(span, "array is too large"));
         }
+        type_name = Type::Array(Box::new(type_name), n);
+    }
+    Ok(type_name)
+}
+
+/// 型 `base` に続く宣言子を読んで, 宣言する名前とその型を返す
+fn build_declarator<'i>(
+    base: Type,
+    inner: &mut pest::iterators::Pairs<'i, Rule>,
+    env: &mut Env,
+) -> Result<(pest::iterators::Pair<'i, Rule>, Type), CompileError> {
+    let x = inner.next().unwrap();
+    if x.as_rule() == Rule::ident {
+        return Ok((x, build_array_type(base, inner, env)?));
     }
+    assert_eq!(x.as_rule(), Rule::funcptr);
+    let mut inner = x.into_inner();
+    inner.next();
+    let name = inner.next().unwrap();
+    // int (*fp[2])(int) は「関数ポインタの長さ2の配列」なので先にポインタの型を作る
+    let params = inner.clone().find(|x| x.as_rule() == Rule::paramtypes);
+    let params = build_param_types(params, env)?;
+    let type_name = Type::Ptr(Box::new(Type::Func(Box::new(base), params)));
+    Ok((name, build_array_type(type_name, &mut inner, env)?))
 }
 
-fn biuld_ast_from_funcdef(pair: pest::iterators::Pair<Rule>) -> Result<FuncDef, Error<Rule>> {
+/// 関数ポインタの引数の型の並び. 引数の名前は読み捨てる
+fn build_param_types(
+    pair: Option<pest::iterators::Pair<Rule>>,
+    env: &mut Env,
+) -> Result<Vec<Type>, CompileError> {
+    let mut params = vec![];
+    let Some(pair) = pair else {
+        return Ok(params);
+    };
+    let mut inner = pair.into_inner();
+    while let Some(typename) = inner.next() {
+        let mut type_name = build_ast_from_typename(typename, env)?;
+        if inner.peek().is_some_and(|x| x.as_rule() != Rule::typename) {
+            type_name = build_declarator(type_name, &mut inner, env)?.1;
+        }
+        params.push(type_name);
+    }
+    Ok(params)
+}
+
+fn build_ast_from_gvar(
+    pair: pest::iterators::Pair<Rule>,
+    env: &mut Env,
+) -> Result<Option<GlobalVar>, CompileError> {
     let mut inner = pair.into_inner();
-    let res_type = build_ast_from_typename(inner.next().unwrap())?;
+    let type_name = build_ast_from_typename(inner.next().unwrap(), env)?;
+    if inner.peek().is_none() {
+        return Ok(None);
+    }
+    let (name, type_name) = build_declarator(type_name, &mut inner, env)?;
+    env.check_redefinition(&name)?;
+
+    check_complete(&type_name, name.as_span().into(), "variable")?;
+    let init = match inner.next() {
+        Some(init) => {
+            let span = init.as_span();
+            if let Type::Array(_, _) | Type::Struct(_) = type_name {
+                return Err(CompileError::new(
+                    span,
+                    "aggregate initializers are not supported",
+                ));
+            }
+            let expr = implicit_cast(build_ast_from_expr(init, env)?, &type_name, "initializing")?;
+            // ポインタは大域変数や文字列, 関数のアドレスでも初期化できる
+            let value = match expr.eval() {
+                Some(v) => Some(GlobalInit::Value(v)),
+                None if type_name.base().is_some() => expr
+                    .eval_address()
+                    .map(|(name, offset)| GlobalInit::Address(name, offset)),
+                None => None,
+            };
+            if value.is_none() {
+                return Err(CompileError::new(
+                    span,
+                    "initializer element is not a compile-time constant",
+                ));
+            }
+            value
+        }
+        None => None,
+    };
+    env.globals.insert(name.as_str().into(), type_name.clone());
+    Ok(Some(GlobalVar::new(name.as_str().into(), type_name, init)))
+}
+
+/// 引数の型と名前の並び
+type Params = Vec<(Type, String)>;
+
+/// 関数の戻り値の型と引数の並びを読んで, 戻り値の型を登録する
+fn build_signature(
+    inner: &mut pest::iterators::Pairs<Rule>,
+    env: &mut Env,
+) -> Result<(String, Params, Span), CompileError> {
+    let res_type = inner.next().unwrap();
+    let res_span = res_type.as_span();
+    let res_type = build_ast_from_typename(res_type, env)?;
+    check_register_passing(&res_type, res_span.into(), "returning")?;
     let name = inner.next().unwrap();
     assert_eq!(name.as_rule(), Rule::ident);
+    let span = Span::from(name.as_span());
     let name: String = name.as_str().into();
-    let mut tmp = inner.next().unwrap();
-    let args = if tmp.as_rule() == Rule::funcindets {
-        let mut a = tmp.into_inner();
-        let mut info = vec![];
+    let mut args = vec![];
+    let params = inner
+        .peek()
+        .filter(|x| matches!(x.as_rule(), Rule::funcindets | Rule::paramtypes));
+    if let Some(x) = params {
+        inner.next();
+        let mut a = x.into_inner();
         while let Some(type_name) = a.next() {
-            let var_name = a.next().unwrap();
-            info.push((
-                build_ast_from_typename(type_name)?,
-                String::from(var_name.as_str()),
-            ));
+            let mut span = type_name.as_span();
+            let mut type_name = build_ast_from_typename(type_name, env)?;
+            // 宣言だけなら引数名は省略できる
+            let mut var_name = "";
+            if a.peek().is_some_and(|x| x.as_rule() != Rule::typename) {
+                let name;
+                (name, type_name) = build_declarator(type_name, &mut a, env)?;
+                span = name.as_span();
+                var_name = name.as_str();
+                if args.iter().any(|(_, x)| x == var_name) {
+                    return Err(CompileError::new(
+                        span,
+                        format!("redefinition of parameter `{}`", var_name),
+                    ));
+                }
+            }
+            check_complete(&type_name, span.into(), "parameter")?;
+            check_register_passing(&type_name, span.into(), "passing")?;
+            args.push((type_name, String::from(var_name)));
         }
-        tmp = inner.next().unwrap();
-        info
-    } else {
-        vec![]
-    };
+    }
+    let params = args.iter().map(|x| x.0.clone()).collect();
+    env.funcs
+        .insert(name.clone(), FuncType { res_type, params });
+    Ok((name, args, span))
+}
+
+fn biuld_ast_from_funcdef(
+    pair: pest::iterators::Pair<Rule>,
+    env: &mut Env,
+) -> Result<FuncDef, CompileError> {
+    let mut inner = pair.into_inner();
+    let (name, args, span) = build_signature(&mut inner, env)?;
+    let res_type = env.funcs[&name].res_type.clone();
+    env.res_type = res_type.clone();
+    let tmp = inner.next().unwrap();
     assert_eq!(tmp.as_rule(), Rule::funcbody);
-    let mut env = HashMap::new();
-    for i in &args {
-        let offset = (env.len() + 1) * 8;
-        env.insert(i.1.clone(), ValInfo::new(offset, i.0.clone()));
+    // 引数と関数本体の一番外側のブロックは同じスコープ
+    env.max_stack = 0;
+    env.enter_scope();
+    let mut params = vec![];
+    for (type_name, var_name) in args {
+        // レジスタから8バイト単位で書き込むので大きさを8の倍数にしておく
+        let offset = env.alloc(type_name.size().next_multiple_of(8), 8);
+        let info = ValInfo::new(offset, type_name);
+        env.scopes
+            .last_mut()
+            .unwrap()
+            .names
+            .insert(var_name, Name::Var(info.clone()));
+        params.push(info);
     }
     let body = tmp
         .into_inner()
-        .into_iter()
-        .map(|x| build_ast_from_stmt(x, &mut env))
-        .collect::<Result<_, _>>()?;
+        .map(|x| build_ast_from_stmt(x, env))
+        .collect::<Result<_, _>>();
+    env.leave_scope();
+    let body = body?;
+    // ラベルは関数全体で有効なので, 未定義かどうかは最後に確かめる
+    let mut undefined = env
+        .labels
+        .drain()
+        .filter(|(_, x)| !x.defined)
+        .filter_map(|(name, x)| Some((x.used?, name)))
+        .collect::<Vec<_>>();
+    undefined.sort_by_key(|x| x.0.start);
+    if let Some((span, name)) = undefined.into_iter().next() {
+        return Err(CompileError::new(
+            span,
+            format!("use of undeclared label `{}`", name),
+        ));
+    }
     // 16の倍数にアラインメントする
-    let mut local_area = env.len();
-    if local_area % 2 == 1 {
-        local_area += 1;
+    let local_area = env.max_stack.next_multiple_of(16);
+    Ok(FuncDef::new(name, res_type, params, body, local_area, span))
+}
+
+/// タグの種類が前の宣言と食い違っているときのエラー
+fn tag_mismatch(tag: &pest::iterators::Pair<Rule>) -> CompileError {
+    CompileError::new(
+        tag.as_span(),
+        format!(
+            "use of `{}` with tag type that does not match previous declaration",
+            tag.as_str()
+        ),
+    )
+}
+
+/// `struct`, `union` の型を読む. 中身があればその場で定義する
+fn build_ast_from_structspec(
+    pair: pest::iterators::Pair<Rule>,
+    env: &mut Env,
+) -> Result<Type, CompileError> {
+    let mut inner = pair.into_inner();
+    let keyword = inner.next().unwrap();
+    assert_eq!(keyword.as_rule(), Rule::structkw);
+    let is_union = keyword.as_str() == "union";
+    let kind = keyword.as_str();
+    let mut tag = None;
+    let mut body = None;
+    for x in inner {
+        match x.as_rule() {
+            Rule::ident => tag = Some(x),
+            _ => body = Some(x),
+        }
+    }
+    let Some(body) = body else {
+        // 中身がなければ既にあるタグを使い, なければ不完全型として宣言する
+        let tag = tag.unwrap();
+        match env.find_tag(tag.as_str()) {
+            Some(Tag::Struct(s)) if s.is_union() == is_union => return Ok(Type::Struct(s)),
+            Some(_) => return Err(tag_mismatch(&tag)),
+            None => {}
+        }
+        let s = Struct::new(Some(tag.as_str().into()), is_union);
+        let scope = env.scopes.last_mut().unwrap();
+        scope
+            .tags
+            .insert(tag.as_str().into(), Tag::Struct(s.clone()));
+        return Ok(Type::Struct(s));
+    };
+    let tag_span = tag.as_ref().map(|x| x.as_span());
+    let s = match tag {
+        Some(tag) => match env.scopes.last().unwrap().tags.get(tag.as_str()) {
+            Some(t) if t.kind() != kind => return Err(tag_mismatch(&tag)),
+            Some(Tag::Struct(s)) if s.is_complete() => {
+                return Err(CompileError::new(
+                    tag.as_span(),
+                    format!("redefinition of `{} {}`", kind, tag.as_str()),
+                ))
+            }
+            Some(Tag::Struct(s)) => s.clone(),
+            Some(Tag::Enum) => unreachable!(),
+            None => {
+                // メンバから自身へのポインタを作れるよう先に登録する
+                let s = Struct::new(Some(tag.as_str().into()), is_union);
+                let scope = env.scopes.last_mut().unwrap();
+                scope
+                    .tags
+                    .insert(tag.as_str().into(), Tag::Struct(s.clone()));
+                s
+            }
+        },
+        None => Struct::new(None, is_union),
+    };
+    let body_span = body.as_span();
+    let mut fields: Vec<(String, Type)> = vec![];
+    for member in body.into_inner() {
+        let mut inner = member.into_inner();
+        let type_name = build_ast_from_typename(inner.next().unwrap(), env)?;
+        let (name, type_name) = build_declarator(type_name, &mut inner, env)?;
+        if fields.iter().any(|(x, _)| x == name.as_str()) {
+            return Err(CompileError::new(
+                name.as_span(),
+                format!("duplicate member `{}`", name.as_str()),
+            ));
+        }
+        check_complete(&type_name, name.as_span().into(), "field")?;
+        fields.push((name.as_str().into(), type_name));
+    }
+    s.define(fields, tag_span.unwrap_or(body_span).into())?;
+    let type_name = Type::Struct(s);
+    if type_name.size() > i32::MAX as usize {
+        return Err(CompileError::new(
+            body_span,
+            format!("`{}` is too large", type_name),
+        ));
     }
-    local_area *= 8;
-    Ok(FuncDef::new(name, res_type, args, body, local_area))
+    Ok(type_name)
 }
 
-fn build_ast_from_typename(pair: pest::iterators::Pair<Rule>) -> Result<Type, Error<Rule>> {
+/// `enum` の型を読む. 列挙定数は今のスコープに置き, 型は int として扱う
+fn build_ast_from_enumspec(
+    pair: pest::iterators::Pair<Rule>,
+    env: &mut Env,
+) -> Result<Type, CompileError> {
+    let mut inner = pair.into_inner();
+    assert_eq!(inner.next().unwrap().as_rule(), Rule::enumkw);
+    let mut tag = None;
+    let mut body = None;
+    for x in inner {
+        match x.as_rule() {
+            Rule::ident => tag = Some(x),
+            _ => body = Some(x),
+        }
+    }
+    if let Some(tag) = tag {
+        let found = match body {
+            Some(_) => env.scopes.last().unwrap().tags.get(tag.as_str()).cloned(),
+            None => env.find_tag(tag.as_str()),
+        };
+        match found {
+            Some(Tag::Enum) if body.is_some() => {
+                return Err(CompileError::new(
+                    tag.as_span(),
+                    format!("redefinition of `enum {}`", tag.as_str()),
+                ))
+            }
+            Some(Tag::Enum) => {}
+            Some(_) => return Err(tag_mismatch(&tag)),
+            None => {
+                let scope = env.scopes.last_mut().unwrap();
+                scope.tags.insert(tag.as_str().into(), Tag::Enum);
+            }
+        }
+    }
+    let Some(body) = body else {
+        return Ok(Type::Int);
+    };
+    // 値を省略した列挙定数は直前の値に1を足したもの
+    let mut value: i64 = 0;
+    for enumerator in body.into_inner() {
+        let mut inner = enumerator.into_inner();
+        let name = inner.next().unwrap();
+        if let Some(expr) = inner.next() {
+            value = eval_integer(build_ast_from_expr(expr, env)?, "enumerator value")?;
+        }
+        let n = i32::try_from(value).map_err(|_| {
+            CompileError::new(
+                name.as_span(),
+                "enumerator value is not representable in `int`",
+            )
+        })?;
+        env.declare_const(name, n)?;
+        value += 1;
+    }
+    Ok(Type::Int)
+}
+
+fn build_ast_from_typename(
+    pair: pest::iterators::Pair<Rule>,
+    env: &mut Env,
+) -> Result<Type, CompileError> {
     assert!(pair.as_rule() == Rule::typename);
     let mut inner = pair.into_inner();
     let base = inner.next().unwrap();
-    assert!(base.as_rule() == Rule::typeident);
-    let mut base = get_type(base.as_str());
-    while inner.next().is_some() {
-        base = Type::Ptr(Box::new(base));   
+    let mut base = match base.as_rule() {
+        Rule::structspec => build_ast_from_structspec(base, env)?,
+        Rule::enumspec => build_ast_from_enumspec(base, env)?,
+        Rule::arithspec => {
+            let specs = base
+                .clone()
+                .into_inner()
+                .map(|x| x.as_str())
+                .collect::<Vec<_>>();
+            get_type(&specs).ok_or_else(|| {
+                CompileError::new(base.as_span(), "invalid combination of type specifiers")
+            })?
+        }
+        _ => match env.find_typedef(base.as_str()).cloned() {
+            Some(t) => t,
+            None => {
+                return Err(CompileError::new(
+                    base.as_span(),
+                    format!("unknown type name `{}`", base.as_str()),
+                ))
+            }
+        },
+    };
+    for x in inner {
+        base = match x.as_rule() {
+            Rule::absfuncptr => {
+                let params = build_param_types(x.into_inner().nth(1), env)?;
+                Type::Ptr(Box::new(Type::Func(Box::new(base), params)))
+            }
+            _ => Type::Ptr(Box::new(base)),
+        };
     }
     Ok(base)
 }
 
-pub fn source_to_ast(source: &str) -> Result<Vec<FuncDef>, Error<Rule>> {
-    let pair = CalcParser::parse(Rule::main, source)?.next().unwrap();
-    let mut pair = pair.into_inner().into_iter().collect::<Vec<_>>();
+pub fn source_to_ast(source: &str) -> Result<Program, CompileError> {
+    let pair = CalcParser::parse(Rule::main, source)
+        .map_err(CompileError::from)?
+        .next()
+        .unwrap();
+    let mut pair = pair.into_inner().collect::<Vec<_>>();
     pair.pop();
-    let v = pair
-        .into_iter()
-        .map(|x| biuld_ast_from_funcdef(x))
-        .collect::<Vec<_>>();
-    v.into_iter().collect::<Result<_, _>>()
+    let mut env = Env {
+        source,
+        scopes: vec![Scope::default()],
+        max_stack: 0,
+        globals: HashMap::new(),
+        funcs: HashMap::new(),
+        res_type: Type::Int,
+        breaks: vec![],
+        continues: vec![],
+        jump_counter: 0,
+        switches: vec![],
+        labels: HashMap::new(),
+        strings: vec![],
+        casts_checked: false,
+    };
+    let mut funcs = vec![];
+    let mut globals = vec![];
+    for x in pair {
+        match x.as_rule() {
+            Rule::funcdef => funcs.push(biuld_ast_from_funcdef(x, &mut env)?),
+            Rule::funcdecl => {
+                build_signature(&mut x.into_inner(), &mut env)?;
+            }
+            Rule::typedef => {
+                build_ast_from_stmt(x, &mut env)?;
+            }
+            _ => globals.extend(build_ast_from_gvar(x, &mut env)?),
+        }
+    }
+    Ok(Program::new(funcs, globals, env.strings))
 }
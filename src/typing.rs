@@ -1,13 +1,324 @@
-#[derive(Debug, Clone)]
+use crate::error::{CompileError, Span};
+use std::cell::OnceCell;
+use std::fmt;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Type {
+    Bool,
+    Char,
+    UChar,
+    Short,
+    UShort,
     Int,
+    UInt,
+    /// `long long` も同じ
+    Long,
+    ULong,
+    Float,
+    /// `long double` も同じ
+    Double,
     Ptr(Box<Type>),
+    Array(Box<Type>, usize),
+    Struct(Rc<Struct>),
+    /// 関数の型. 戻り値と引数の型
+    Func(Box<Type>, Vec<Type>),
+}
+
+/// 構造体のメンバ. `offset` は先頭からのバイト数
+#[derive(Debug, Clone)]
+pub struct Member {
+    pub name: String,
+    pub type_name: Type,
+    pub offset: usize,
+}
+
+/// 構造体または共用体の型. 宣言だけされて中身がまだ決まっていないこともある
+#[derive(Debug)]
+pub struct Struct {
+    tag: Option<String>,
+    is_union: bool,
+    layout: OnceCell<Layout>,
+}
+
+#[derive(Debug)]
+struct Layout {
+    members: Vec<Member>,
+    size: usize,
+    align: usize,
+}
+
+/// 構造体の型はタグが同じでも宣言ごとに別物
+impl PartialEq for Struct {
+    fn eq(&self, other: &Struct) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Struct {
+    pub fn new(tag: Option<String>, is_union: bool) -> Rc<Struct> {
+        Rc::new(Struct {
+            tag,
+            is_union,
+            layout: OnceCell::new(),
+        })
+    }
+
+    pub fn is_union(&self) -> bool {
+        self.is_union
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.layout.get().is_some()
+    }
+
+    /// メンバを並べて中身を決める. 各メンバは自身のアラインメントに揃え,
+    /// 全体の大きさは最大のアラインメントの倍数にする. 共用体のメンバはすべて先頭に置く.
+    /// メンバの中で同じタグが定義されていて既に中身が決まっていればエラーにする
+    pub fn define(
+        self: &Rc<Self>,
+        fields: Vec<(String, Type)>,
+        span: Span,
+    ) -> Result<(), CompileError> {
+        let mut members = vec![];
+        let mut size: usize = 0;
+        let mut align = 1;
+        for (name, type_name) in fields {
+            let offset = if self.is_union {
+                0
+            } else {
+                size.next_multiple_of(type_name.align())
+            };
+            size = size.max(offset + type_name.size());
+            align = align.max(type_name.align());
+            members.push(Member {
+                name,
+                type_name,
+                offset,
+            });
+        }
+        let layout = Layout {
+            members,
+            size: size.next_multiple_of(align),
+            align,
+        };
+        self.layout.set(layout).map_err(|_| {
+            CompileError::new(
+                span,
+                format!("nested redefinition of `{}`", Type::Struct(self.clone())),
+            )
+        })
+    }
+
+    pub fn member(&self, name: &str) -> Option<&Member> {
+        self.layout.get()?.members.iter().find(|x| x.name == name)
+    }
+}
+
+impl Type {
+    pub fn size(&self) -> usize {
+        match self {
+            // gcc と同じく関数の大きさは1とする
+            Type::Bool | Type::Char | Type::UChar | Type::Func(_, _) => 1,
+            Type::Short | Type::UShort => 2,
+            Type::Int | Type::UInt | Type::Float => 4,
+            Type::Long | Type::ULong | Type::Double | Type::Ptr(_) => 8,
+            Type::Array(t, n) => t.size() * n,
+            Type::Struct(s) => s.layout.get().map_or(0, |x| x.size),
+        }
+    }
+
+    pub fn align(&self) -> usize {
+        match self {
+            Type::Array(t, _) => t.align(),
+            Type::Struct(s) => s.layout.get().map_or(1, |x| x.align),
+            _ => self.size(),
+        }
+    }
+
+    /// ポインタや配列の指す先の型
+    pub fn base(&self) -> Option<&Type> {
+        match self {
+            Type::Ptr(t) | Type::Array(t, _) => Some(t),
+            _ => None,
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(
+            self,
+            Type::Bool
+                | Type::Char
+                | Type::UChar
+                | Type::Short
+                | Type::UShort
+                | Type::Int
+                | Type::UInt
+                | Type::Long
+                | Type::ULong
+        )
+    }
+
+    /// 符号なしで比較や除算をする型. ポインタも含む
+    pub fn is_unsigned(&self) -> bool {
+        matches!(
+            self,
+            Type::Bool | Type::UChar | Type::UShort | Type::UInt | Type::ULong | Type::Ptr(_)
+        )
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self, Type::Float | Type::Double)
+    }
+
+    /// 算術型. 整数か浮動小数点数
+    pub fn is_arith(&self) -> bool {
+        self.is_integer() || self.is_float()
+    }
+
+    /// 算術演算やキャストの対象になる型
+    pub fn is_scalar(&self) -> bool {
+        self.is_arith() || matches!(self, Type::Ptr(_))
+    }
+
+    /// 浮動小数点数を含む型. 構造体ならメンバも調べる
+    pub fn has_float(&self) -> bool {
+        match self {
+            Type::Array(t, _) => t.has_float(),
+            Type::Struct(s) => s
+                .layout
+                .get()
+                .is_some_and(|x| x.members.iter().any(|m| m.type_name.has_float())),
+            t => t.is_float(),
+        }
+    }
+
+    /// 整数拡張. int より小さい整数型は int にする
+    pub fn promote(&self) -> Type {
+        match self {
+            t if t.is_integer() && t.size() < 4 => Type::Int,
+            t => t.clone(),
+        }
+    }
+
+    /// 64 ビットの値をこの型に変換した値. 符号なしならゼロ拡張, 符号付きなら符号拡張した値になる
+    pub fn wrap(&self, n: i64) -> i64 {
+        match self {
+            Type::Bool => (n != 0) as i64,
+            Type::Char => n as i8 as i64,
+            Type::UChar => n as u8 as i64,
+            Type::Short => n as i16 as i64,
+            Type::UShort => n as u16 as i64,
+            Type::Int => n as i32 as i64,
+            Type::UInt => n as u32 as i64,
+            _ => n,
+        }
+    }
+
+    /// 大きさの決まっていない型. 変数やメンバにはできない
+    pub fn is_incomplete(&self) -> bool {
+        match self {
+            Type::Struct(s) => !s.is_complete(),
+            Type::Array(t, _) => t.is_incomplete(),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Bool => write!(f, "_Bool"),
+            Type::Char => write!(f, "char"),
+            Type::UChar => write!(f, "unsigned char"),
+            Type::Short => write!(f, "short"),
+            Type::UShort => write!(f, "unsigned short"),
+            Type::Int => write!(f, "int"),
+            Type::UInt => write!(f, "unsigned int"),
+            Type::Long => write!(f, "long"),
+            Type::ULong => write!(f, "unsigned long"),
+            Type::Float => write!(f, "float"),
+            Type::Double => write!(f, "double"),
+            Type::Ptr(t) => match &**t {
+                Type::Func(res, params) => write!(f, "{} (*)({})", res, join(params)),
+                t => write!(f, "{}*", t),
+            },
+            Type::Array(t, n) => write!(f, "{}[{}]", t, n),
+            Type::Struct(s) => {
+                let kind = if s.is_union { "union" } else { "struct" };
+                match &s.tag {
+                    Some(tag) => write!(f, "{} {}", kind, tag),
+                    None => write!(f, "{} <anonymous>", kind),
+                }
+            }
+            Type::Func(res, params) => write!(f, "{} ({})", res, join(params)),
+        }
+    }
+}
+
+/// 型の並びをコンマで区切った文字列
+fn join(types: &[Type]) -> String {
+    types
+        .iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
-pub fn get_type(name: &str) -> Type {
-    if name == "int" {
-        return Type::Int;
+/// 通常の算術型変換. 浮動小数点数があれば大きい方の浮動小数点型にする.
+/// 整数どうしなら整数拡張したうえで大きい方の型にそろえ, 同じ大きさなら符号なしを選ぶ
+pub fn usual_arith(l: &Type, r: &Type) -> Type {
+    if l.is_float() || r.is_float() {
+        return if *l == Type::Double || *r == Type::Double {
+            Type::Double
+        } else {
+            Type::Float
+        };
+    }
+    let (l, r) = (l.promote(), r.promote());
+    if l.size() != r.size() {
+        return if l.size() > r.size() { l } else { r };
+    }
+    if r.is_unsigned() {
+        r
     } else {
-        panic!("type name `{}` is not exit!", name);
+        l
+    }
+}
+
+/// `unsigned long int` のような型指定子の並びが表す型. 組み合わせがおかしければ None
+pub fn get_type(specs: &[&str]) -> Option<Type> {
+    let count = |name: &str| specs.iter().filter(|x| **x == name).count();
+    // 浮動小数点型は float, double, long double だけ
+    if count("float") + count("double") > 0 {
+        return match specs {
+            ["float"] => Some(Type::Float),
+            ["double"] | ["long", "double"] | ["double", "long"] => Some(Type::Double),
+            _ => None,
+        };
+    }
+    let unsigned = count("unsigned");
+    let signs = unsigned + count("signed");
+    let int = count("int");
+    if signs > 1 || int > 1 {
+        return None;
+    }
+    let ty = match (count("_Bool"), count("char"), count("short"), count("long")) {
+        (1, 0, 0, 0) if signs + int == 0 => Type::Bool,
+        (0, 1, 0, 0) if int == 0 => Type::Char,
+        (0, 0, 1, 0) => Type::Short,
+        (0, 0, 0, 0) => Type::Int,
+        (0, 0, 0, 1 | 2) => Type::Long,
+        _ => return None,
+    };
+    if unsigned == 0 {
+        return Some(ty);
     }
+    Some(match ty {
+        Type::Char => Type::UChar,
+        Type::Short => Type::UShort,
+        Type::Int => Type::UInt,
+        Type::Long => Type::ULong,
+        _ => unreachable!(),
+    })
 }
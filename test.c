@@ -13,3 +13,29 @@ int test6(int a, int b, int c, int d, int e, int f) {
 int print(int n) {
     printf("%d\n", n);
 }
+
+struct pair { int a; int b; };
+struct tiny { char a; char b; char c; };
+struct mixed { char c; long x; };
+
+int pairsum(struct pair p) { return p.a * 10 + p.b; }
+
+struct pair pairmake(int a, int b) {
+    struct pair p = {a, b};
+    return p;
+}
+
+int tinysum(int x, struct tiny t) { return x + t.a * 100 + t.b * 10 + t.c; }
+
+struct tiny tinymake(char a, char b, char c) {
+    struct tiny t = {a, b, c};
+    return t;
+}
+
+int mixedsum(struct mixed m) { return m.c + m.x; }
+
+double fmix(int a, double x, float y, long b) { return a + x * 10 + y * 100 + b * 1000; }
+
+float fhalf(float x) { return x / 2; }
+
+int apply(int (*f)(int, int), int a, int b) { return f(a, b); }
@@ -3,15 +3,35 @@ assert() {
   expected="$1"
   input="$2"
 
-  target/debug/rust-9cc "$input" tmp.s
-  cc -o tmp tmp.s test.o
-  ./tmp
+  for mode in asm link; do
+    if [ $mode = asm ]; then
+      printf '%s\n' "$input" | target/debug/rust-9cc -S -o tmp.s - || exit 1
+      cc -o tmp tmp.s test.o -lm
+    else
+      printf '%s\n' "$input" | target/debug/rust-9cc -o tmp - test.o || exit 1
+    fi
+    ./tmp
+    actual="$?"
+
+    if [ "$actual" = "$expected" ]; then
+      echo "$input => $actual ($mode)"
+    else
+      echo "$input => $expected expected, but got $actual ($mode)"
+      exit 1
+    fi
+  done
+}
+
+assert_error() {
+  input="$1"
+
+  printf '%s\n' "$input" | target/debug/rust-9cc -S -o tmp.s - 2> /dev/null
   actual="$?"
 
-  if [ "$actual" = "$expected" ]; then
-    echo "$input => $actual"
+  if [ "$actual" = "1" ]; then
+    echo "$input => compile error"
   else
-    echo "$input => $expected expected, but got $actual"
+    echo "$input => compile error expected, but got $actual"
     exit 1
   fi
 }
@@ -82,13 +102,585 @@ assert 7 "int f(int a, int b, int c, int d, int e, int f) { int x; a = a * b; x
 assert 13 "int f(int n) { if(n <= 1) return 1; else return f(n - 2) + f(n - 1); } int main() { return f(6); }"
 assert 5 "int main() { int a; int* b; a = 5; b = &a; return *b; }"
 assert 10 "int main() { int a; int* b; int** c; int*** d; a = 5; b = &a; c = &b; d = &c; return test2(a, ***d); }"
-assert 2 "int main() {int a; int ba; a = 5; ba = 2; return *(&a-8); }" # this is implemention defined
-assert 5 "int main() { int a; int ba; a = 5; ba = 2; return *(&ba+8); }" # this is implemention defined
+assert 2 "int main() {int a; int ba; a = 5; ba = 2; return *(&a-1); }" # this is implemention defined
+assert 5 "int main() { int a; int ba; a = 5; ba = 2; return *(&ba+1); }" # this is implemention defined
 assert 42 "int main() {int a; int *b; a = 3; b = &a; *b = 42; return a; } "
 assert 21 "int main() {int a; int *b; int **c; a = 3; b = &a; c = &b; **c = 21; return *(&a); } "
 assert 5 "int main() { int a; int *b; a = 2; b = &a; **(&b) = 5; return a; } "
 assert 91 "int f(int* a) { return *a; } int main() { int a; a = 91; return f(&a); } "
-assert 3 "int main() {int a; int ba; ba = 2; *(&a-8) = 3; return ba; }" # this is implemention defined
-assert 5 "int main() { int a; int ba; a = 4; *(&ba+8) = 5; return a; }" # this is implemention defined
+assert 3 "int main() {int a; int ba; ba = 2; *(&a-1) = 3; return ba; }" # this is implemention defined
+assert 5 "int main() { int a; int ba; a = 4; *(&ba+1) = 5; return a; }" # this is implemention defined
+assert 5 "int main() { int a[10]; a[3] = 5; return a[3]; }"
+assert 3 "int main() { int a[2]; *a = 1; *(a + 1) = 2; return a[0] + a[1]; }"
+assert 21 "int main() { int a[3]; int i; int s; for (i = 0; i < 3; i = i + 1) a[i] = i + 6; s = 0; for (i = 0; i < 3; i = i + 1) s = s + a[i]; return s; }"
+assert 12 "int main() { int a[2][3]; int i; int j; for (i = 0; i < 2; i = i + 1) for (j = 0; j < 3; j = j + 1) a[i][j] = i * j + i; return a[1][2] + a[1][0] + a[1][1] + a[0][2] + 6; }"
+assert 7 "int main() { int a[4]; int *p; p = a; p[2] = 7; return a[2]; }"
+assert 9 "int main() { int x; int a[5]; int y; x = 4; y = 5; a[0] = 0; a[4] = 0; return x + y; }"
+assert 4 "int main() { int a[6]; int *p; int *q; p = a + 1; q = a + 5; return q - p; }"
+assert 6 "int main() { int a[3]; a[0] = 4; a[1] = 5; a[2] = 6; return *(2 + a); }"
+assert 5 "int main() { int a[3]; int *p; a[1] = 5; p = a + 2; return *(p - 1); }"
+assert 8 "int main() { int a[3]; a[2] = 8; return 2[a]; }"
+assert 3 "int main() { char x; x = 3; return x; }"
+assert 1 "int main() { char x; char y; int z; x = 1; y = 2; z = 3; return x; }"
+assert 5 "int main() { char a[3]; a[0] = 1; a[1] = 2; a[2] = 5; return a[2]; }"
+assert 1 "int main() { char a[4]; char *p; p = a; return &a[1] - p; }"
+assert 3 "int main() { char a[2]; int x; x = 3; a[0] = 9; a[1] = 9; return x; }"
+assert 4 "int main() { char x; x = -4; return -x; }"
+assert 44 "int main() { char x; x = 300; return x; }"
+assert 2 "int main() { char x[3]; int y; y = 0; x[0] = 1; *(x + 1) = 2; return x[1] + y; }"
+assert 7 "int f(char a, char b) { return a - b; } int main() { return f(10, 3); }"
+assert 98 'int main() { return "abc"[1]; }'
+assert 0 'int main() { return "abc"[3]; }'
+assert 111 'int main() { char *s; s = "hello"; return s[4]; }'
+assert 10 'int main() { return "\n"[0]; }'
+assert 65 'int main() { return "\x41"[0]; }'
+assert 65 'int main() { return "\101"[0]; }'
+assert 34 'int main() { return "a\"b"[1]; }'
+assert 92 'int main() { return "\\"[0]; }'
+assert 0 'int main() { return "\0"[0]; }'
+assert 14 'int main() { return printf("hello, %s!\n", "world"); }'
+assert 7 'int main() { return 3 + printf("%d\n", 42) + 1; }'
+assert 0 "int x; int main() { return x; }"
+assert 3 "int x; int main() { x = 3; return x; }"
+assert 7 "int x; int y; int main() { x = 3; y = 4; return x + y; }"
+assert 5 "int x[4]; int main() { x[0] = 2; x[3] = 3; return x[0] + x[3]; }"
+assert 8 "int g; int f() { g = 8; return 0; } int main() { f(); return g; }"
+assert 42 "int g = 42; int main() { return g; }"
+assert 6 "int g = 2 * 3; char c = 1; int main() { return g + c - 1; }"
+assert 3 "int x; int main() { int x; x = 3; return x; }"
+assert 2 "int *p; int a[3]; int main() { p = a; p[1] = 2; return a[1]; }"
+assert 4 "char s[4]; int main() { s[0] = 1; s[3] = 3; return s[0] + s[3]; }"
+assert 255 "int g = -1; char c = -1; int main() { return (g == -1) * 255 + (c + 1); }"
+assert 7 "int x; int *p = &x; int main() { x = 7; return *p; }"
+assert 7 "int a[4]; int *p = a + 2; int *q = &a[3] - 2; int main() { a[1] = 3; a[2] = 4; return *p + *q; }"
+assert 3 "char *s = \"abc\"; char *t = \"xyz\" + 1; int main() { return (s[2] == 'c') + (t[0] == 'y') * 2; }"
+assert 6 "struct P { int u; int v; } g; int *p = &g.v; int main() { g.v = 6; return *p; }"
+assert 4 "int main() { return sizeof(int); }"
+assert 1 "int main() { return sizeof(char); }"
+assert 8 "int main() { return sizeof(char *); }"
+assert 12 "int main() { return sizeof(int[3]); }"
+assert 12 "int main() { return sizeof(char[2][6]); }"
+assert 4 "int main() { int x; return sizeof x; }"
+assert 4 "int main() { int x; return sizeof(x); }"
+assert 20 "int main() { int a[5]; return sizeof(a); }"
+assert 4 "int main() { int a[5]; return sizeof a[0]; }"
+assert 4 "int main() { int a[5]; return sizeof(a[0]); }"
+assert 8 "int main() { int a[5]; return sizeof(a + 1); }"
+assert 4 "int main() { return sizeof \"abc\"; }"
+assert 1 "int main() { char *p; return sizeof *p; }"
+assert 3 "int main() { int x; x = 3; sizeof(x = 5); return x; }"
+assert 8 "int main() { return sizeof(int) * 2; }"
+assert 3 "int main() { int sizeofx; sizeofx = 3; return sizeofx; }"
+assert 9 "int g[3]; int main() { return sizeof(g) / sizeof(g[0]) * 3; }"
+assert 2 "int main() { int x; x = 2; { int x; x = 3; } return x; }"
+assert 3 "int main() { int x; x = 2; { x = 3; } return x; }"
+assert 5 "int main() { int x; x = 2; { int x; x = 3; { int x; x = 5; return x; } } }"
+assert 7 "int main() { int x; x = 7; { char x; x = 1; } { int *x; x = 0; } return x; }"
+assert 3 "int main() { int x; x = 1; { int y; y = 2; x = x + y; } { int z; z = 5; } return x; }"
+assert 6 "int f(int a) { { int a; a = 5; } return a; } int main() { return f(6); }"
+assert 4 "int g; int main() { g = 4; { int g; g = 1; } return g; }"
+assert 6 "int main() { int i; int s; s = 0; for (i = 0; i < 4; i = i + 1) { int t; t = i; s = s + t; } return s; }"
+assert 3 "int main() { struct { int a; int b; } x; x.a = 1; x.b = 2; return x.a + x.b; }"
+assert 8 "int main() { struct { int a; int b; } x; return sizeof(x); }"
+assert 8 "int main() { struct { char a; int b; } x; return sizeof(x); }"
+assert 2 "int main() { struct { char a; char b; } x; return sizeof(x); }"
+assert 12 "int main() { struct { char a; int b; char c; } x; return sizeof(x); }"
+assert 4 "int main() { struct { char a; int b; } x; char *p; char *q; p = &x.b; q = &x.a; return p - q; }"
+
+assert 24 "int main() { struct { int a[3]; char b[10]; } x; return sizeof(x); }"
+assert 6 "int main() { struct { int a[3]; } x; x.a[0] = 1; x.a[2] = 5; return x.a[0] + x.a[2]; }"
+assert 7 "struct point { int x; int y; }; int main() { struct point p; struct point *q; q = &p; q->x = 3; q->y = 4; return p.x + p.y; }"
+assert 5 "int main() { struct s { int a; struct s *next; } x; struct s y; x.next = &y; y.a = 5; return x.next->a; }"
+assert 9 "int main() { struct { struct { char c; int v; } in; int w; } o; o.in.v = 4; o.w = 5; return o.in.v + o.w; }"
+assert 3 "int main() { struct p { int a; int b; } x; struct p y; x.a = 1; x.b = 2; y = x; return y.a + y.b; }"
+assert 12 "int main() { struct p { char s[3]; int n; } x; struct p y; x.s[2] = 7; x.n = 5; y = x; return y.s[2] + y.n; }"
+assert 8 "int main() { struct p { int a; } x[3]; x[2].a = 8; return x[2].a; }"
+assert 24 "int main() { struct p { int a; int b; } x[3]; return sizeof(x); }"
+assert 10 "struct p { int a; int b; } g; int main() { g.a = 3; g.b = 7; return g.a + g.b; }"
+assert 1 "struct p { int a; }; int main() { struct p { char c; } x; return sizeof(x); }"
+assert 4 "struct p { int a; }; int main() { { struct p { char c; }; } struct p x; return sizeof(x); }"
+assert 16 "int main() { struct { struct { int a; int b; } x[2]; } s; return sizeof s; }"
+assert 1 "int main() { struct t; struct t *p; struct t { int a; }; return sizeof(*p) == 4; }"
+assert 12 "struct pair { int a; int b; }; int pairsum(struct pair p); int main() { struct pair p; p.a = 1; p.b = 2; return pairsum(p); }"
+assert 45 "struct pair { int a; int b; }; struct pair pairmake(int a, int b); int main() { struct pair p; p = pairmake(4, 5); return p.a * 10 + p.b; }"
+assert 45 "struct pair { int a; int b; }; struct pair pairmake(int a, int b); int main() { return pairmake(4, 5).a * 10 + pairmake(4, 5).b; }"
+assert 124 "struct tiny { char a; char b; char c; }; int tinysum(int x, struct tiny t); int main() { struct tiny t; t.a = 1; t.b = 2; t.c = 3; return tinysum(1, t); }"
+assert 123 "struct tiny { char a; char b; char c; }; struct tiny tinymake(char a, char b, char c); int main() { struct tiny t; t = tinymake(1, 2, 3); return t.a * 100 + t.b * 10 + t.c; }"
+assert 42 "struct mixed { char c; long x; }; int mixedsum(struct mixed m); int main() { struct mixed m; m.c = 2; m.x = 40; return mixedsum(m); }"
+assert 21 "struct p { int a; int b; }; struct p swap(struct p x) { struct p y; y.a = x.b; y.b = x.a; return y; } int main() { struct p x; x.a = 1; x.b = 2; x = swap(x); return x.a * 10 + x.b; }"
+assert 36 "struct t { char a; char b; char c; }; int f(int x, struct t s, int y) { return x + s.a + s.b + s.c + y; } int main() { struct t s; s.a = 5; s.b = 6; s.c = 7; return f(8, s, 10); }"
+assert 15 "struct p { int a; int b; }; int f(struct p x, struct p y, int z) { return x.a + x.b + y.a + y.b + z; } int main() { struct p x; x.a = 1; x.b = 2; return f(x, x, 9); }"
+assert 4 "int main() { union { int a; char b; } x; return sizeof(x); }"
+assert 12 "int main() { union { char a[12]; char b; } x; return sizeof(x); }"
+assert 12 "int main() { union { char a[9]; int b; } x; return sizeof x; }"
+assert 3 "int main() { union { int a; char b; } x; x.a = 515; return x.b; }"
+assert 1 "int main() { union { int a; char b[8]; } x; x.a = 0; x.b[1] = 1; return x.a == 256; }"
+assert 7 "union u { int a; struct { char c; char d; } s; }; int main() { union u x; union u *p; p = &x; p->s.d = 7; return x.s.d; }"
+assert 4 "int main() { union u { int a; int b; } x; union u y; x.a = 4; y = x; return y.b; }"
+assert 0 "int main() { enum { A, B, C }; return A; }"
+assert 2 "int main() { enum { A, B, C }; return C; }"
+assert 12 "int main() { enum { A = 5, B, C = 10, D }; return B + D - 5; }"
+assert 4 "int main() { enum e { A, B } x; x = B; return sizeof(x) * x; }"
+assert 3 "enum color { RED = 1, GREEN, BLUE }; int main() { enum color c; c = BLUE; return c; }"
+assert 4 "enum { N = 2 * 2 }; int g = N; int main() { return g; }"
+assert 5 "int main() { enum { X = 3 }; { enum { X = 5 }; return X; } }"
+assert 3 "int main() { enum { X = 3 }; { int X; X = 7; } return X; }"
+assert 7 "int main() { enum { X = 3 }; { int X; X = 7; return X; } }"
+assert 2 "enum { A, B, C, }; int main() { return C; }"
+assert 1 "int main() { enum { A = -1, B = A + 2 }; return B; }"
+assert 12 "enum { N = 3 }; int main() { int a[N]; return sizeof(a); }"
+assert 16 "enum { N = 3 }; int g[N + 1]; int main() { return sizeof(g); }"
+assert 24 "int main() { enum { N = 2 }; int a[(N) * 3]; return sizeof a; }"
+assert 6 "int main() { return sizeof(char[2][1 ? 3 : 4]); }"
+assert 3 "typedef int myint; int main() { myint x; x = 3; return x; }"
+assert 5 "typedef int *intp; int main() { int a; intp p; p = &a; *p = 5; return a; }"
+assert 12 "typedef int arr[3]; int main() { arr x; return sizeof(x); }"
+assert 24 "typedef int arr[3]; int main() { arr x[2]; return sizeof x; }"
+assert 4 "typedef int T; int main() { return sizeof(T); }"
+assert 8 "typedef int T; int main() { return sizeof(T *); }"
+assert 6 "int main() { typedef struct { int a; int b; } P; P p; p.a = 2; p.b = 4; return p.a + p.b; }"
+assert 7 "typedef struct node Node; struct node { int v; Node *next; }; int main() { Node a; Node b; a.next = &b; b.v = 7; return a.next->v; }"
+assert 6 "int main() { int a; int b; a = 2; b = 3; a * b; return a * b; }"
+assert 6 "typedef int T; int main() { int x; x = 2; { int T; T = 3; T * x; return T * x; } }"
+assert 3 "typedef int T; int main() { { typedef char T; T c; c = 3; return sizeof(c) * c; } }"
+assert 4 "typedef int T; int main() { { typedef char T; } T c; return sizeof(c); }"
+assert 2 "typedef int T; int main() { T T; T = 2; return T; }"
+assert 4 "int main() { int x; x = 4; x; return x; }"
+assert 8 "typedef int T; int f(T x) { return x * 2; } int main() { return f(4); }"
+assert 1 "typedef char C; int main() { C *p; return sizeof(*p); }"
+assert 3 "int main() { int i; i = 0; while (1) { if (i == 3) break; i = i + 1; } return i; }"
+assert 5 "int main() { int i; for (i = 0; ; i = i + 1) { if (i == 5) break; } return i; }"
+assert 25 "int main() { int i; int s; s = 0; for (i = 0; i < 10; i = i + 1) { if (i - i / 2 * 2 == 0) continue; s = s + i; } return s; }"
+assert 20 "int main() { int i; int s; i = 0; s = 0; while (i < 10) { i = i + 1; if (i - i / 2 * 2 == 1) continue; s = s + i; } return s + 10 - 20; }"
+assert 12 "int main() { int i; int j; int s; s = 0; for (i = 0; i < 4; i = i + 1) { for (j = 0; j < 10; j = j + 1) { if (j == 3) break; s = s + 1; } } return s; }"
+assert 10 "int main() { int i; int j; int s; s = 0; for (i = 0; i < 5; i = i + 1) { for (j = 0; j < 5; j = j + 1) { if (j > 1) continue; s = s + 1; } } return s; }"
+assert 4 "int main() { int i; i = 0; for (;;) { i = i + 1; if (i < 4) continue; break; } return i; }"
+assert 7 "int main() { int breakx; breakx = 7; return breakx; }"
+assert 5 "int main() { int i; i = 0; do i = i + 1; while (i < 5); return i; }"
+assert 1 "int main() { int i; i = 0; do { i = i + 1; } while (0); return i; }"
+assert 13 "int main() { int i; int s; i = 0; s = 0; do { i = i + 1; if (i == 2) continue; if (i > 5) break; s = s + i; } while (i < 10); return s; }"
+assert 20 "int main() { int x; x = 2; switch (x) { case 1: return 10; case 2: return 20; case 3: return 30; } return 0; }"
+assert 0 "int main() { int x; x = 7; switch (x) { case 1: return 10; case 2: return 20; } return 0; }"
+assert 9 "int main() { int x; x = 7; switch (x) { case 1: return 10; default: return 9; case 2: return 20; } return 0; }"
+assert 6 "int main() { int x; int s; x = 1; s = 0; switch (x) { case 1: s = s + 1; case 2: s = s + 2; case 3: s = s + 3; break; case 4: s = s + 4; } return s; }"
+assert 31 "int main() { int i; int s; s = 0; for (i = 0; i < 8; i = i + 1) { switch (i) { case 0: s = s + 1; break; case 1: s = s + 2; break; case 2: s = s + 3; break; case 4: s = s + 5; break; case 5: continue; default: s = s + 10; } } return s - 10; }"
+assert 3 "int main() { int x; x = 0 - 2; switch (x) { case 0 - 2: return 3; case 0 - 1: return 4; case 0: return 5; case 1: return 6; } return 0; }"
+assert 42 "int main() { int x; x = 100; switch (x) { case 1: return 1; case 2: return 2; case 3: return 3; case 4: return 4; } return 42; }"
+assert 8 "enum { A, B, C, D }; int main() { int r; r = 0; switch (C) { case A: r = 1; break; case B: r = 2; break; case C: r = 8; break; case D: r = 16; break; } return r; }"
+assert 4 "int main() { int x; int y; x = 1; y = 2; switch (x) { case 1: switch (y) { case 1: return 3; case 2: return 4; } return 5; } return 6; }"
+assert 2 "int main() { switch (3) { default: return 2; } return 0; }"
+assert 3 "int main() { int x; x = 1; goto skip; x = 2; skip: x = x + 2; return x; }"
+assert 10 "int main() { int i; i = 0; again: i = i + 1; if (i < 10) goto again; return i; }"
+assert 7 "int f() { goto out; out: return 3; } int main() { goto out; return 1; out: return f() + 4; }"
+assert 5 "int main() { int i; int j; for (i = 0; i < 5; i = i + 1) for (j = 0; j < 5; j = j + 1) if (i * j == 6) goto done; done: return i + j; }"
+assert 4 "int main() { int x; x = 0; { int y; y = 4; goto end; } end: return 4; }"
+assert 9 "int main() { int r; r = 0; goto cleanup; fail: return 1; cleanup: r = 9; return r; }"
+assert 2 "int x; int main() { x: x = 2; return x; }"
+assert 6 "int main() { a: b: return 6; }"
+assert 1 "int main() { return 1 && 2; }"
+assert 0 "int main() { return 1 && 0; }"
+assert 0 "int main() { return 0 && 1; }"
+assert 1 "int main() { return 0 || 3; }"
+assert 0 "int main() { return 0 || 0; }"
+assert 1 "int main() { return 2 || 0; }"
+assert 1 "int main() { return !0; }"
+assert 0 "int main() { return !5; }"
+assert 1 "int main() { return !!7; }"
+assert 0 "int main() { int x; x = 0; if (0 && (x = 1)) return 9; return x; }"
+assert 0 "int main() { int x; x = 0; if (1 || (x = 1)) return x; return 9; }"
+assert 6 "int main() { int x; x = 5; return (0 && (x = 1) || x) + x; }"
+assert 1 "int main() { return 1 || 0 && 0; }"
+assert 0 "int main() { return (1 || 0) && 0; }"
+assert 1 "int main() { int *p; p = 0; return !p; }"
+assert 1 "int main() { return 1 < 2 && 3 > 2; }"
+assert 4 "int main() { int i; i = 0; while (i < 10 && i != 4) i = i + 1; return i; }"
+assert 2 "int main() { switch (2) { case 0 || 0: return 1; case 1 && 2 || 0: return 3; case !0 + 1: return 2; } return 0; }"
+assert 2 "int main() { return 17 % 5; }"
+assert 1 "int main() { return 7 % 3 * 1; }"
+assert 1 "int main() { int x; x = 0 - 7; return x % 3 + 2; }"
+assert 2 "int main() { return 6 & 3; }"
+assert 7 "int main() { return 6 | 3; }"
+assert 5 "int main() { return 6 ^ 3; }"
+assert 0 "int main() { return ~0 + 1; }"
+assert 6 "int main() { return ~(0 - 7); }"
+assert 40 "int main() { return 5 << 3; }"
+assert 5 "int main() { return 40 >> 3; }"
+assert 1 "int main() { int x; x = 0 - 8; return (x >> 1) == 0 - 4; }"
+assert 12 "int main() { return 1 + 2 << 2; }"
+assert 1 "int main() { return 1 << 2 == 4; }"
+assert 1 "int main() { return 1 | 2 ^ 3 & 2; }"
+assert 1 "int main() { return 1 & 1 && 2 | 0; }"
+assert 1 "int main() { return 3 & 1 == 1; }"
+assert 32 "int main() { int i; i = 5; return 1 << i; }"
+assert 4 "int main() { switch (9) { case 1 << 3 | 1: return 4; } return 0; }"
+assert 4 "int main() { int a[4]; return sizeof(a) >> 2; }"
+assert 7 "int main() { int x; x = 5; x += 2; return x; }"
+assert 3 "int main() { int x; x = 5; x -= 2; return x; }"
+assert 15 "int main() { int x; x = 5; x *= 3; return x; }"
+assert 2 "int main() { int x; x = 7; x /= 3; return x; }"
+assert 1 "int main() { int x; x = 7; x %= 3; return x; }"
+assert 2 "int main() { int x; x = 6; x &= 3; return x; }"
+assert 7 "int main() { int x; x = 6; x |= 3; return x; }"
+assert 5 "int main() { int x; x = 6; x ^= 3; return x; }"
+assert 24 "int main() { int x; x = 3; x <<= 3; return x; }"
+assert 3 "int main() { int x; x = 24; x >>= 3; return x; }"
+assert 10 "int main() { int x; int y; x = 3; y = (x += 2) * 2; return y; }"
+assert 9 "int main() { int x; int y; x = y = 3; x += y += 3; return x; }"
+assert 6 "int main() { int x; x = 5; return ++x; }"
+assert 4 "int main() { int x; x = 5; return --x; }"
+assert 5 "int main() { int x; x = 5; return x++; }"
+assert 6 "int main() { int x; x = 5; x++; return x; }"
+assert 5 "int main() { int x; x = 5; return x--; }"
+assert 4 "int main() { int x; x = 5; x--; return x; }"
+assert 11 "int main() { int x; x = 5; return x++ + x; }"
+assert 45 "int main() { int i; int s; s = 0; for (i = 0; i < 10; i++) s += i; return s; }"
+assert 3 "int main() { int a[4]; int *p; a[0] = 1; a[1] = 2; a[2] = 3; p = a; p += 2; return *p; }"
+assert 2 "int main() { int a[4]; int *p; a[0] = 1; a[1] = 2; a[2] = 3; p = a + 2; p--; return *p; }"
+assert 1 "int main() { int a[4]; int *p; a[0] = 1; a[1] = 2; p = a; return *p++; }"
+assert 2 "int main() { int a[4]; int *p; a[0] = 1; a[1] = 2; p = a; return *++p; }"
+assert 3 "int main() { int a[4]; int i; a[0] = 1; a[1] = 2; a[2] = 3; i = 0; a[i++] += 2; return a[0] + i - i; }"
+assert 1 "int main() { int a[4]; int i; i = 0; a[0] = 0; a[1] = 0; a[i++] += 1; return i; }"
+assert 2 "int main() { char s[4]; char *p; s[0] = 1; s[1] = 2; p = s; p++; return *p; }"
+assert 3 "struct s { int a; int b; }; int main() { struct s x; struct s *p; p = &x; p->b = 2; p->b++; return x.b + x.a - x.a; }"
+assert 4 "int main() { int x; x = 3; x++; return x--; }"
+assert 5 "int main() { int x; x = 4; return x+++1; }"
+assert 2 "int main() { return 1 ? 2 : 3; }"
+assert 3 "int main() { return 0 ? 2 : 3; }"
+assert 4 "int main() { int x; x = 5; return x > 3 ? x - 1 : x + 1; }"
+assert 3 "int main() { return 0 ? 1 : 0 ? 2 : 3; }"
+assert 2 "int main() { return 1 ? 0 ? 1 : 2 : 3; }"
+assert 5 "int main() { int x; int y; x = 0; y = 0; 1 ? (x = 5) : (y = 5); return x + y; }"
+assert 7 "int main() { int x; x = 1 ? 7 : 9; return x; }"
+assert 2 "int main() { int a[2]; int b[2]; int *p; a[1] = 2; b[1] = 3; p = 1 ? a : b; return p[1]; }"
+assert 3 "int main() { int a[2]; a[0] = 3; return *(0 ? 0 : a); }"
+assert 6 "struct s { int a; }; int main() { struct s x; struct s y; x.a = 5; y.a = 6; return (0 ? x : y).a; }"
+assert 3 "int main() { return (1, 2, 3); }"
+assert 5 "int main() { int x; int y; x = (y = 2, y + 3); return x; }"
+assert 25 "int main() { int i; int j; int s; s = 0; for (i = 0, j = 10; i < j; i++, j--) s += j - i; return s - 5; }"
+assert 4 "int main() { int a[8]; a[3] = 4; return a[1, 3]; }"
+assert 3 "int f(int a, int b) { return a + b; } int main() { int x; return f((x = 1, 2), 1); }"
+assert 4 "int main() { switch (2) { case 1 ? 2 : 3: return 4; } return 0; }"
+assert 5 "int main() { int x; x = 0; if (x++, x) return 5; return 6; }"
+assert 4 "int main() { return sizeof(0 ? 1 : 2); }"
+assert 44 "int main() { return (char)300; }"
+assert 1 "int main() { return (char)257 == 1; }"
+assert 1 "int main() { return (char)255 < 0; }"
+assert 3 "int main() { int x; x = 3; return (int)x; }"
+assert 2 "int main() { int x; x = 3; return (x) - 1; }"
+assert 6 "int main() { int x; x = 3; return (x) * 2; }"
+assert 1 "int main() { int x; x = 3; return (x) & 1; }"
+assert 8 "int main() { int x; x = 3; return 2 * (x) + 2; }"
+assert 4 "int main() { int a[2]; a[1] = 4; return (a)[1]; }"
+assert 5 "int main() { int x; x = 4; return (x)++ + 1; }"
+assert 7 "int f(int x) { return x + 1; } int main() { return (f)(6); }"
+assert 2 "typedef int T; int main() { T x; x = 3; return (T)x - 1; }"
+assert 2 "typedef char T; int main() { return (T)258; }"
+assert 3 "int main() { int x; x = 3; { int T; T = 1; return (x) * (T) + (T) - 1; } }"
+assert 3 "typedef int T; int main() { int x; x = 3; { int T; T = 1; return (x) * (T); } }"
+assert 7 "int main() { char s[4]; int *p; s[0] = 7; p = (int *)s; return *(char *)p; }"
+assert 1 "int main() { int x; char *p; x = 1; p = (char *)&x; return *p; }"
+assert 4 "int main() { return sizeof((char)1 + 1); }"
+assert 1 "int main() { return sizeof((char)1); }"
+assert 8 "int main() { return sizeof((int *)0); }"
+assert 2 "int main() { int x; x = 2; return (char)(int)x; }"
+assert 50 "int main() { int x; x = 0; return ((((((((((((((((((((((((((((((((((((((((((((((((((x + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1) + 1); }"
+assert 2 "int main() { int x; x = 5; return (((((x) - 1) - (x)) + (x)) - (char)2); }"
+assert 44 "int main() { char c; return c = 300; }"
+assert 44 "int main() { char c; c = 300; return c; }"
+assert 1 "int main() { char c; c = 127; c++; return c == 0 - 128; }"
+assert 1 "int main() { char c; c = 127; return ++c == 0 - 128; }"
+assert 44 "char f() { return 300; } int main() { return f(); }"
+assert 44 "int f(char c) { return c; } int main() { return f(300); }"
+assert 3 "int main() { switch (258) { case (char)258: return 1; default: return 3; } }"
+assert 2 "int main() { switch ((char)258) { case (char)258: return 2; } return 3; }"
+assert 2 "int main() { return sizeof(short); }"
+assert 8 "int main() { return sizeof(long); }"
+assert 8 "int main() { return sizeof(long long); }"
+assert 1 "int main() { return sizeof(_Bool); }"
+assert 4 "int main() { return sizeof(unsigned); }"
+assert 2 "int main() { return sizeof(unsigned short int); }"
+assert 8 "int main() { return sizeof(long unsigned int); }"
+assert 8 "int main() { return sizeof(1 + (long)1); }"
+assert 4 "int main() { short s; return sizeof(s + s); }"
+assert 15 "int main() { return (unsigned)-1 >> 28; }"
+assert 255 "int main() { return -1 >> 28; }"
+assert 0 "int main() { return -1 < (unsigned)0; }"
+assert 1 "int main() { return (unsigned)0 - 1 > 0; }"
+assert 1 "int main() { return -1 < 0; }"
+assert 1 "int main() { long x; x = -1; return x < (unsigned)0; }"
+assert 7 "int main() { unsigned x; x = -1; return x / 613566756; }"
+assert 3 "int main() { unsigned x; x = -1; return x % 4; }"
+assert 1 "int main() { _Bool b; b = 256; return b; }"
+assert 0 "int main() { _Bool b; b = 0; return b; }"
+assert 1 "int main() { _Bool b; b = 2; b++; return b; }"
+assert 44 "int main() { short s; s = 65580; return s; }"
+assert 1 "int main() { short s; s = 65535; return s == -1; }"
+assert 255 "int main() { unsigned char c; c = -1; return c; }"
+assert 1 "int main() { unsigned short s; s = -1; return s == 65535; }"
+assert 1 "int main() { int x; x = 2147483647; x++; return x < 0; }"
+assert 1 "int main() { return (int)((long)65536 * 65536 + 1) == 1; }"
+assert 1 "int main() { long x; x = 2147483647; x++; return x > 0; }"
+assert 1 "int main() { int x; x = -1; unsigned long y; y = x; return y == -1; }"
+assert 1 "int main() { unsigned x; x = -1; long y; y = x; return y == (long)65536 * 65536 - 1; }"
+assert 12 "int main() { struct { char a; short b; int c; long d; } x; return (char *)&x.d - (char *)&x.a + 4; }"
+assert 16 "int main() { struct { char a; short b; int c; long d; } x; return sizeof x; }"
+assert 6 "int main() { short a[3]; return sizeof a; }"
+assert 4 "int main() { short a[3]; a[0] = 1; a[1] = 2; a[2] = -1; return a[0] + a[1] + (a[2] == -1); }"
+assert 1 "int main() { unsigned char c; c = 200; return c + c == 400; }"
+assert 1 "int main() { switch ((unsigned)-1) { case -1: return 1; } return 0; }"
+assert 2 "int main() { unsigned x; x = -1; switch (x) { case (long)65536 * 65536 - 1: return 2; } return 0; }"
+assert 1 "int main() { unsigned x; x = 1; x <<= 31; return x > 0; }"
+assert 1 "int main() { unsigned x; x = 0; return ~x == 4294967295u; }"
+assert 15 "int main() { unsigned x; x = 0; return (~x) >> 28; }"
+assert 1 "int main() { unsigned char c; c = 0; return ~c == -1; }"
+assert 1 "int main() { unsigned char c; c = 255; return (~c) >> 8 == -1; }"
+assert 1 "int main() { int x; x = 1; x <<= 31; return x < 0; }"
+assert 42 "int main() { long long x; x = 42; return x; }"
+assert 3 "int f(short a, unsigned char b) { return a + b; } int main() { return f(65537, 258); }"
+assert 1 "unsigned f() { return -1; } int main() { return f() > 0; }"
+assert 31 "int main() { return 0x1F; }"
+assert 171 "int main() { return 0XaB; }"
+assert 1 "int main() { return 0755 == 493; }"
+assert 0 "int main() { return 0; }"
+assert 5 "int main() { return 0b101; }"
+assert 10 "int main() { return 10UL; }"
+assert 8 "int main() { return sizeof(10L); }"
+assert 8 "int main() { return sizeof(10ull); }"
+assert 4 "int main() { return sizeof(10u); }"
+assert 4 "int main() { return sizeof(2147483647); }"
+assert 8 "int main() { return sizeof(2147483648); }"
+assert 4 "int main() { return sizeof(0xffffffff); }"
+assert 8 "int main() { return sizeof(0x100000000); }"
+assert 1 "int main() { return 0xffffffff > 0; }"
+assert 1 "int main() { return -1 < 2147483648; }"
+assert 0 "int main() { return -1 < 0u; }"
+assert 1 "int main() { return 4294967296 / 65536 == 65536; }"
+assert 1 "int main() { long x; x = 0x7fffffffffffffff; return x > 0 && x + 1 < 0; }"
+assert 1 "int main() { return 18446744073709551615u == -1; }"
+assert 3 "int main() { long x; x = 4294967299; return x; }"
+assert 4 "int main() { int a[0x4]; return sizeof(a) / sizeof(a[0]); }"
+assert 97 "int main() { return 'a'; }"
+assert 10 "int main() { return '\\n'; }"
+assert 0 "int main() { return '\\0'; }"
+assert 39 "int main() { return '\\''; }"
+assert 1 "int main() { return '\\xff' == -1; }"
+assert 4 "int main() { return sizeof('a'); }"
+assert 1 "int main() { char *s; s = \"abc\"; return s[1] == 'b'; }"
+assert 4 "int main() { return sizeof(float); }"
+assert 8 "int main() { return sizeof(double); }"
+assert 8 "int main() { return sizeof(long double); }"
+assert 3 "int main() { return 1.5 * 2; }"
+assert 2 "int main() { return (int)2.9; }"
+assert 254 "int main() { return (int)-2.9; }"
+assert 3 "int main() { return -1.5 * -2; }"
+assert 1 "int main() { return .5 + .5 == 1; }"
+assert 1 "int main() { return 1e2 == 100 && 2.5e-1 == 0.25 && 1.E1 == 10; }"
+assert 1 "int main() { return 1.5f == 1.5; }"
+assert 0 "int main() { return 0.1f == 0.1; }"
+assert 4 "int main() { return sizeof(1.0f); }"
+assert 8 "int main() { return sizeof(1.0); }"
+assert 4 "int main() { return sizeof(1L + 1.0f); }"
+assert 8 "int main() { return sizeof(1.0f + 1.0); }"
+assert 2 "int main() { double x; x = 1; x = x / 4; return x * 8; }"
+assert 1 "int main() { return 1.0 / 3 < 0.34 && 2.5 >= 2.5 && -1.5 < 0 && 3 > 2.5 && !(2.5 <= 2.4); }"
+assert 2 "int main() { double z; double n; z = 0.0; n = z / z; return (n == n) + (n != n) * 2 + (n < 1) * 4 + (n >= 1) * 8; }"
+assert 1 "int main() { if (0.5) return 1; return 0; }"
+assert 2 "int main() { if (-0.0) return 1; return 2; }"
+assert 1 "int main() { return !0.0 && !-0.0 && 0.5 && !!2.0f; }"
+assert 10 "int main() { double x; int n; x = 1; n = 0; while (x < 1000) { x = x * 2; n++; } return n; }"
+assert 3 "int main() { unsigned u; u = 3.9; return u; }"
+assert 1 "int main() { _Bool b; b = 0.1; return b; }"
+assert 1 "int main() { float f; f = 16777217; return f == 16777216; }"
+assert 10 "int main() { long l; l = 1e10; return l / 1000000000; }"
+assert 1 "int main() { double d; d = (unsigned)-1; return d == 4294967295.0; }"
+assert 1 "int main() { float f; double d; f = 0.1; d = f; return d == 0.1f && d != 0.1; }"
+assert 1 "int main() { double d; d = 1; d += 0.5; d *= 4; d -= 1; d /= 5; return d; }"
+assert 2 "int main() { float f; f = 1; f++; ++f; f--; return f; }"
+assert 3 "int main() { int i; i = 1; i += 2.9; return i; }"
+assert 6 "double f(double x, int n) { return x * n; } int main() { return f(1.5, 4); }"
+assert 36 "float h(float a, float b, float c, float d, float e, float f, float g, float i) { return a + b + c + d + e + f + g + i; } int main() { return h(1, 2, 3, 4, 5, 6, 7, 8); }"
+assert 34 "double m(int a, double b, int c, double d) { return a * 1000 + b * 100 + c * 10 + d; } int main() { return m(1, 2, 3, 4) - 1200; }"
+assert 121 "double fmix(int a, double x, float y, long b); int main() { return fmix(1, 2, 1, 0); }"
+assert 10 "float fhalf(float x); int main() { return fhalf(5) * 4; }"
+assert 7 "double sqrt(double x); int main() { return sqrt(49); }"
+assert 7 "double sqrt(double); int main() { return sqrt(49); }"
+assert 3 "int strlen(char *); int main() { return strlen(\"abc\"); }"
+assert 7 "int main() { char buf[16]; sprintf(buf, \"%.2f\", 2.5); return buf[0] + buf[2] + buf[3] - 3 * '0'; }"
+assert 6 "int main() { char buf[16]; sprintf(buf, \"%.1f\", 1.5f); return buf[0] + buf[2] - 2 * '0'; }"
+assert 5 "double g = 2.5; int main() { return g * 2; }"
+assert 5 "float g = 1.25f; int main() { return g * 4; }"
+assert 2 "int g = 2.7; int main() { return g; }"
+assert 2 "int main() { return (1 ? 1 : 2.5) * 2; }"
+assert 8 "int main() { return sizeof(1 ? 1 : 2.5); }"
+assert 1 "int main() { switch (2) { case (int)2.5: return 1; } return 0; }"
+assert 1 "int main() { unsigned long u; double d; u = 18446744073709551615ul; d = u; return d > 0; }"
+assert 1 "int main() { unsigned long u; double d; u = 18446744073709551615ul; d = u; return d == 18446744073709551616.0; }"
+assert 1 "int main() { unsigned long u; float f; u = 9223372036854775809ul; f = u; return f == 9223372036854775808.0f; }"
+assert 1 "int main() { unsigned long u; double d; u = 9223372036854776833ul; d = u; return d == 9223372036854777856.0; }"
+assert 3 "int main() { unsigned long u; double d; u = 3; d = u; return d; }"
+assert 10 "int main() { double x; unsigned long u; x = 1e19; u = x; return u / 1000000000000000000; }"
+assert 1 "int main() { double x; unsigned long u; x = 9223372036854775808.0; u = x; return u == 9223372036854775808ul; }"
+assert 7 "int main() { double x; unsigned long u; x = 7.5; u = x; return u; }"
+assert 1 "int main() { unsigned long u; u = 18446744073709551615ul; u /= 2.0; return u == 9223372036854775808ul; }"
+assert 7 "int add(int a, int b) { return a + b; } int main() { int (*fp)(int, int); fp = add; return fp(3, 4); }"
+assert 7 "int add(int a, int b) { return a + b; } int main() { int (*fp)(int, int); fp = &add; return (*fp)(3, 4); }"
+assert 12 "int add(int a, int b) { return a + b; } int mul(int a, int b) { return a * b; } int main() { int (*ops[2])(int, int); ops[0] = add; ops[1] = mul; return ops[0](3, 4) - ops[1](1, 7) + ops[1](3, 4); }"
+assert 5 "int sub(int a, int b) { return a - b; } int main() { return apply(sub, 8, 3); }"
+assert 5 "int apply(int (*)(int, int), int, int); int sub(int a, int b) { return a - b; } int main() { return apply(sub, 8, 3); }"
+assert 5 "int sub(int a, int b) { return a - b; } int (*f)(int, int) = sub; int main() { return f(8, 3); }"
+assert 4 "int abs(int); int (*f)(int) = abs; int main() { return f(0 - 4); }"
+assert 3 "int cmp(int *a, int *b) { return *a - *b; } int main() { int a[4]; a[0] = 3; a[1] = 1; a[2] = 4; a[3] = 2; qsort(a, 4, sizeof(int), cmp); return a[0] * 100 + a[1] * 10 + a[2] == 123 ? a[2] : 0; }"
+assert 9 "typedef int (*binop)(int, int); int add(int a, int b) { return a + b; } int main() { binop f; f = add; return f(4, 5); }"
+assert 6 "struct S { int (*f)(int); int x; }; int twice(int a) { return a * 2; } int main() { struct S s; s.f = twice; s.x = 3; return s.f(s.x); }"
+assert 8 "int twice(int a) { return a * 2; } int (*g)(int); int main() { g = twice; return (**g)(4); }"
+assert 1 "int twice(int a) { return a * 2; } int main() { int (*fp)(int); fp = twice; return fp == twice && fp == &twice; }"
+assert 8 "int main() { return sizeof(int (*)(int)); }"
+assert 5 "double half(double x) { return x / 2; } int main() { double (*h)(double); h = half; return h(10); }"
+assert 4 "int twice(int a) { return a * 2; } int call(int (*f)(int), int x) { return f(x); } int main() { return call(twice, 2); }"
+assert 3 "int one() { return 1; } int two() { return 2; } int main() { int x; x = 1; return (x ? one : two)() + (x ? two : one)(); }"
+assert 7 "int add(int a, int b) { return a + b; } int main() { int (*fp)(int, int); fp = (int (*)(int, int))add; return fp(3, 4); }"
+assert 6 "typedef int (*fpt)(int); int g; int set(int a) { g = a; return 0; } fpt get(int n) { g = n; return set; } int main() { int a; int *p; a = 5; p = &a; get(*p)(g + 1); return g; }"
+assert 5 "int abs(int x); int main() { int (*f)(int); f = abs; return f(0 - 5); }"
+assert 3 "long strlen(char *s); int main() { long (*f)(char *); f = &strlen; return f(\"abc\"); }"
+
+assert_error "int main() { int *p; int *q; return p + q; }"
+assert_error "int main() { int *p; return 1 - p; }"
+assert_error "int main() { return sizeof(y); }"
+assert_error "int main() { return sizeof(foo *); }"
+assert_error "int main() { int x; int x; return 0; }"
+assert_error "int main() { { int y; } return y; }"
+assert_error "int f(int a, int a) { return a; } int main() { return 0; }"
+assert_error "int f(int) { return 0; } int main() { return 0; }"
+assert_error "int f(int a, int a); int main() { return 0; }"
+assert_error "int main() { struct { int a; } x; return x.b; }"
+assert_error "int main() { int x; return x.a; }"
+assert_error "int main() { struct { int a; } x; return x->a; }"
+assert_error "int main() { struct s x; return 0; }"
+assert_error "int main() { struct s { int a; int a; } x; return 0; }"
+assert_error "int main() { struct s { int a; }; struct s { int b; }; return 0; }"
+assert_error "int main() { struct s { struct s { int a; } x; } y; return 0; }"
+assert_error "int main() { struct { int a; } x; return x + 1; }"
+assert_error "int main() { struct { int a; } x; struct { int a; } y; x = y; return 0; }"
+assert_error "int main() { struct { int a; } x; x = 1; return 0; }"
+assert_error "struct big { long a; long b; long c; }; int f(struct big b) { return 0; } int main() { return 0; }"
+assert_error "struct p { long a; long b; }; int f(struct p a, struct p b, struct p c, struct p d) { return 0; } int main() { return 0; }"
+assert_error "int main() { struct s { int a; }; union s x; return 0; }"
+assert_error "int main() { enum s { A }; struct s *x; return 0; }"
+assert_error "int main() { union s { int a; }; union s { int b; }; return 0; }"
+assert_error "int main() { enum { A, A }; return 0; }"
+assert_error "int main() { int A; enum { A }; return 0; }"
+assert_error "int A; enum { A }; int main() { return 0; }"
+assert_error "enum { A }; int A; int main() { return 0; }"
+assert_error "int main() { int x; enum { A = x }; return 0; }"
+assert_error "int main() { enum { A = 2147483647, B }; return 0; }"
+assert_error "int main() { enum { A }; A = 1; return 0; }"
+assert_error "int main() { foo x; return 0; }"
+assert_error "int main() { foo * x; return 0; }"
+assert_error "typedef int T; int main() { return T; }"
+assert_error "typedef int T; int T; int main() { return 0; }"
+assert_error "int main() { typedef int T; int T; return 0; }"
+assert_error "int main() { { typedef int T; } T x; return 0; }"
+assert_error "int main() { break; return 0; }"
+assert_error "int main() { if (1) continue; return 0; }"
+assert_error "int main() { while (1) {} { break; } return 0; }"
+assert_error "int main() { case 1: return 0; }"
+assert_error "int main() { default: return 0; }"
+assert_error "int main() { int x; switch (1) { case x: return 0; } return 0; }"
+assert_error "int main() { switch (1) { case 1: case 1: return 0; } return 0; }"
+assert_error "int main() { switch (1) { default: default: return 0; } return 0; }"
+assert_error "int main() { do return 0; while (1) }"
+assert_error "int main() { goto nowhere; return 0; }"
+assert_error "int main() { a: a: return 0; }"
+assert_error "int main() { a: return 0; } int f() { goto a; }"
+assert_error "int main() { goto: return 0; }"
+assert_error "int main() { int *p; return p % 2; }"
+assert_error "int main() { int *p; return p & 1; }"
+assert_error "int main() { int *p; return 1 << p; }"
+assert_error "int main() { int *p; return ~p; }"
+assert_error "int main() { int x; return *x; }"
+assert_error "int main() { double d; return *d; }"
+assert_error "int main() { switch (1) { case 1 % 0: return 0; } return 0; }"
+assert_error "int main() { int *p; p *= 2; return 0; }"
+assert_error "int main() { int x; int *p; x += p; return 0; }"
+assert_error "int main() { int *p; int *q; p += q; return 0; }"
+assert_error "struct s { int a; }; int main() { struct s x; x++; return 0; }"
+assert_error "int main() { 1++; return 0; }"
+assert_error "int main() { int x; ++x = 1; return 0; }"
+assert_error "struct s { int a; }; int main() { struct s x; return 1 ? x : 1; }"
+assert_error "int main() { int x; (1 ? x : x) = 1; return 0; }"
+assert_error "int main() { int x; (0, x) = 1; return 0; }"
+assert_error "int main() { return 1 ? 2; }"
+assert_error "int main() { int a[(1, 2)]; return 0; }"
+assert_error "struct s { int a; }; int main() { struct s x; return (int)x; }"
+assert_error "struct s { int a; }; int main() { return (struct s)1; }"
+assert_error "int main() { return (foo)1; }"
+assert_error "struct s { int a; }; int f(struct s x) { return 0; } int main() { return f(1); }"
+assert_error "struct s { int a; }; struct s f() { return 1; } int main() { return 0; }"
+assert_error "int f(int a, int b); int main() { return f(1); }"
+assert_error "int f(int a); int main() { return f(1, 2); }"
+assert_error "int main() { long char x; return 0; }"
+assert_error "int main() { unsigned _Bool b; return 0; }"
+assert_error "int main() { signed unsigned x; return 0; }"
+assert_error "int main() { short long x; return 0; }"
+assert_error "int main() { long long long x; return 0; }"
+assert_error "int main() { return 08; }"
+assert_error "int main() { return 0b102; }"
+assert_error "int main() { return 0x; }"
+assert_error "int main() { return 10lu2; }"
+assert_error "int main() { return 10lL; }"
+assert_error "int main() { return 10uu; }"
+assert_error "int main() { return 12abc; }"
+assert_error "int main() { return 18446744073709551616; }"
+assert_error "int main() { return ''; }"
+assert_error "int main() { return 'ab'; }"
+assert_error "int main() { return 1.5 % 2; }"
+assert_error "int main() { return 1.5 << 1; }"
+assert_error "int main() { double d; d = 1; return ~d; }"
+assert_error "int main() { double d; d = 1; d |= 1; return 0; }"
+assert_error "int main() { int *p; double d; p = p + d; return 0; }"
+assert_error "int main() { int *p; double d; d = p; return 0; }"
+assert_error "int main() { int *p; p = (int *)1.5; return 0; }"
+assert_error "int main() { int *p; return (double)p; }"
+assert_error "int main() { switch (1.5) { } return 0; }"
+assert_error "int main() { switch (1) { case 1.5: return 1; } return 0; }"
+assert_error "int main() { long float x; return 0; }"
+assert_error "int main() { unsigned double x; return 0; }"
+assert_error "struct p { double x; }; int f(struct p a) { return 0; } int main() { return 0; }"
+assert_error "int main() { return 1e; }"
+assert_error "int main() { int x; return x(1); }"
+assert_error "int f(int a) { return a; } int main() { int (*fp)(int); fp = f; return fp(1, 2); }"
+assert_error "int main() { int (*fp)(int); double d; fp = (int (*)(int))d; return 0; }"
+assert_error "int main() { int *p; return (*p)(); }"
+assert_error "int f() { return 0; } int g() { return 1; } int main() { f = g; return 0; }"
+assert_error "int f() { return 0; } int main() { f += 1; return 0; }"
+assert_error "int f() { return 0; } int main() { f++; return 0; }"
+assert_error "int f() { return 0; } int main() { --*f; return 0; }"
+
+assert_error "int main() { int a; return a[1]; }"
+assert_error "int main() { int a[2]; int b[2]; a = b; return 0; }"
+assert_error "int main() { int a[2]; a++; return 0; }"
+assert_error "int main() { int a[2]; a += 1; return 0; }"
+assert_error "int main() { int a[0x7fffffffffffffff]; return 0; }"
+assert_error "int main() { int a[100000000000]; return 0; }"
+assert_error "int main() { int n; int a[n]; return 0; }"
+assert_error "int main() { int a[1.5]; return 0; }"
+assert_error "int main() { int a[-1]; return 0; }"
+assert_error "int main() { char a[2000000000]; char b[2000000000]; return 0; }"
+assert_error "struct S { char a[2000000000]; char b[2000000000]; }; int main() { return 0; }"
+assert_error "int main() { foo a; return 1; }"
+assert_error "int main() { return b; }"
+assert_error "int main() { 1 = 2; }"
+assert_error "int main() { return test6(1, 2, 3, 4, 5, 6, 7); }"
+assert_error "int f(int a, int b, int c, int d, int e, int f, int g) { return 0; }"
+assert_error "int main() { return 1 + ; }"
+assert_error "int x; int x; int main() { return 0; }"
+assert_error "int a[2] = 1; int main() { return 0; }"
+assert_error "int x; int *p = &x + x; int main() { return 0; }"
+assert_error "int x; long n = (long)&x; int main() { return 0; }"
+assert_error "int y; int x = y; int main() { return 0; }"
+assert_error "int main() { return x; } int x;"
+assert_error 'int main() { return "\x"[0]; }'
+assert_error 'int main() { return "\x100"[0]; }'
+assert_error 'int main() { return "abc; }'
 
 echo OK
\ No newline at end of file